@@ -0,0 +1,191 @@
+use super::escape::{escape_orbit, Fractal};
+use super::geometry::View;
+use super::sampling::unit_hash;
+use num::Complex;
+use std::sync::Mutex;
+
+const SAMPLE_RADIUS: f64 = 2.0;
+const BATCH_SIZE: u64 = 4096;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Density {
+    pub samples: u64,
+    pub seed: u64,
+    pub limits: Vec<u32>,
+}
+
+type Counts = Vec<Vec<u32>>;
+
+fn sample_point(seed: u64, index: u64) -> Complex<f64> {
+    Complex {
+        re: (unit_hash(seed, 2 * index) * 2.0 - 1.0) * SAMPLE_RADIUS,
+        im: (unit_hash(seed, 2 * index + 1) * 2.0 - 1.0) * SAMPLE_RADIUS,
+    }
+}
+
+fn trace_samples(
+    counts: &mut Counts,
+    samples: std::ops::Range<u64>,
+    view: View,
+    fractal: Fractal,
+    density: &Density,
+) {
+    let limit = density.limits.iter().cloned().max().unwrap_or(0);
+    let mut orbit = Vec::new();
+    for index in samples {
+        let point = sample_point(density.seed, index);
+        let count = match escape_orbit(fractal, point, limit) {
+            Some((count, _)) => count,
+            None => continue,
+        };
+
+        orbit.clear();
+        let (mut z, c) = fractal.start(point);
+        for _ in 0..=count {
+            z = fractal.step(z, c);
+            if let Some((column, row)) = view.point_to_pixel(z) {
+                orbit.push(row * view.bounds.0 + column);
+            }
+        }
+
+        for (channel, &channel_limit) in counts.iter_mut().zip(density.limits.iter()) {
+            if count < channel_limit {
+                for &pixel in &orbit {
+                    channel[pixel] += 1;
+                }
+            }
+        }
+    }
+}
+
+fn render_batches(
+    batches: std::ops::Range<u64>,
+    view: View,
+    fractal: Fractal,
+    density: &Density,
+    accumulators: &Mutex<Vec<Counts>>,
+) {
+    if batches.end - batches.start > 1 {
+        let middle = batches.start + (batches.end - batches.start) / 2;
+        let render_half = |half| render_batches(half, view, fractal, density, accumulators);
+        rayon::join(
+            || render_half(batches.start..middle),
+            || render_half(middle..batches.end),
+        );
+        return;
+    }
+
+    let counts = accumulators.lock().unwrap().pop();
+    let pixels = view.bounds.0 * view.bounds.1;
+    let mut counts = counts.unwrap_or_else(|| vec![vec![0; pixels]; density.limits.len()]);
+    let start = batches.start * BATCH_SIZE;
+    let end = (batches.end * BATCH_SIZE).min(density.samples);
+    trace_samples(&mut counts, start..end, view, fractal, density);
+    accumulators.lock().unwrap().push(counts);
+}
+
+pub fn render_density(view: View, fractal: Fractal, density: &Density) -> Counts {
+    let batches = density.samples.div_ceil(BATCH_SIZE);
+    let accumulators = Mutex::new(Vec::new());
+    render_batches(0..batches.max(1), view, fractal, density, &accumulators);
+
+    let mut accumulators = accumulators.into_inner().unwrap();
+    let mut counts = accumulators.pop().unwrap();
+    for other in accumulators {
+        for (channel, other) in counts.iter_mut().zip(other) {
+            for (count, other) in channel.iter_mut().zip(other) {
+                *count += other;
+            }
+        }
+    }
+    counts
+}
+
+pub fn density_values(counts: &[u32]) -> Vec<Option<f32>> {
+    counts
+        .iter()
+        .map(|&count| Some((count as f32).sqrt()))
+        .collect()
+}
+
+pub fn density_pixels(counts: &[Vec<u32>]) -> Vec<u8> {
+    let channels = counts.len();
+    let pixels = counts.first().map_or(0, Vec::len);
+    let mut bytes = vec![0; pixels * channels];
+    for (channel, counts) in counts.iter().enumerate() {
+        let max = counts.iter().cloned().max().unwrap_or(0).max(1) as f64;
+        for (pixel, &count) in counts.iter().enumerate() {
+            bytes[pixel * channels + channel] = ((count as f64 / max).sqrt() * 255.0).round() as u8;
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::geometry::Rotation;
+    use super::*;
+    use rayon::{Configuration, ThreadPool};
+
+    fn view() -> View {
+        View {
+            bounds: (40, 30),
+            upper_left: Complex { re: -2.0, im: 1.5 },
+            lower_right: Complex { re: 1.0, im: -1.5 },
+            rotation: Rotation::none(),
+        }
+    }
+
+    fn density(limits: Vec<u32>) -> Density {
+        Density {
+            samples: 20_000,
+            seed: 7,
+            limits,
+        }
+    }
+
+    #[test]
+    fn point_to_pixel_inverts_sample_to_point() {
+        let rotated = View {
+            rotation: Rotation::new(Complex { re: -0.5, im: 0.0 }, 30.0),
+            ..view()
+        };
+        let point = rotated.sample_to_point((13.5, 21.5));
+        assert_eq!(rotated.point_to_pixel(point), Some((13, 21)));
+
+        let outside = Complex { re: 1.5, im: 0.0 };
+        assert_eq!(view().point_to_pixel(outside), None);
+    }
+
+    #[test]
+    fn density_is_seeded_and_thread_independent() {
+        let render = |threads, density: &Density| {
+            let pool = ThreadPool::new(Configuration::new().set_num_threads(threads)).unwrap();
+            pool.install(|| render_density(view(), Fractal::Mandelbrot, density))
+        };
+
+        let single = render(1, &density(vec![50]));
+        assert_eq!(single, render(3, &density(vec![50])));
+        assert!(single[0].iter().any(|&count| count > 0));
+
+        let mut reseeded = density(vec![50]);
+        reseeded.seed = 8;
+        assert_ne!(single, render(1, &reseeded));
+    }
+
+    #[test]
+    fn nebulabrot_channels_grow_with_their_limits() {
+        let counts = render_density(view(), Fractal::Mandelbrot, &density(vec![10, 100, 1000]));
+        let totals: Vec<u64> = counts
+            .iter()
+            .map(|channel| channel.iter().map(|&count| count as u64).sum())
+            .collect();
+        assert!(totals[0] < totals[1] && totals[1] < totals[2]);
+
+        let pixels = density_pixels(&counts);
+        assert_eq!(pixels.len(), 40 * 30 * 3);
+        for channel in 0..3 {
+            assert_eq!(pixels.iter().skip(channel).step_by(3).max(), Some(&255));
+        }
+    }
+}
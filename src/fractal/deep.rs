@@ -0,0 +1,244 @@
+use num::{BigInt, Complex, ToPrimitive, Zero};
+use std::ops::{Add, Mul, Sub};
+
+const MAX_EXPONENT: i64 = 4096;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fixed {
+    mantissa: BigInt,
+    bits: usize,
+}
+
+impl Fixed {
+    pub fn parse(s: &str, bits: usize) -> Option<Fixed> {
+        let s = s.trim();
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (digits, exponent) = match digits.find(['e', 'E']) {
+            Some(index) => (&digits[..index], digits[index + 1..].parse::<i64>().ok()?),
+            None => (digits, 0),
+        };
+        if exponent.abs() > MAX_EXPONENT {
+            return None;
+        }
+        let (integer, fraction) = match digits.find('.') {
+            Some(index) => (&digits[..index], &digits[index + 1..]),
+            None => (digits, ""),
+        };
+        if integer.is_empty() && fraction.is_empty() {
+            return None;
+        }
+        if !integer
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+
+        let numerator: BigInt = format!("0{}{}", integer, fraction).parse().ok()?;
+        let scale = exponent - fraction.len() as i64;
+        let power = num::pow(BigInt::from(10), scale.unsigned_abs() as usize);
+        let mantissa = if scale >= 0 {
+            (numerator * power) << bits
+        } else {
+            (numerator << bits) / power
+        };
+
+        Some(Fixed {
+            mantissa: if negative { -mantissa } else { mantissa },
+            bits,
+        })
+    }
+
+    pub fn from_f64(value: f64, bits: usize) -> Fixed {
+        let (mantissa, exponent, sign) = num::Float::integer_decode(value);
+        let mut mantissa = BigInt::from(mantissa);
+        let shift = bits as i64 + exponent as i64;
+        mantissa = if shift >= 0 {
+            mantissa << shift as usize
+        } else {
+            mantissa >> (-shift) as usize
+        };
+
+        Fixed {
+            mantissa: if sign < 0 { -mantissa } else { mantissa },
+            bits,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        if self.mantissa.is_zero() {
+            return 0.0;
+        }
+
+        let excess = (self.mantissa.bits() as usize)
+            .saturating_sub(64)
+            .min(self.bits);
+        let top = (&self.mantissa >> excess).to_f64().unwrap_or(0.0);
+        top * 2f64.powi(excess as i32 - self.bits as i32)
+    }
+}
+
+pub fn parse_center(s: &str, bits: usize) -> Option<(Fixed, Fixed)> {
+    let index = s.find(',')?;
+    Some((
+        Fixed::parse(&s[..index], bits)?,
+        Fixed::parse(&s[index + 1..], bits)?,
+    ))
+}
+
+impl Add for &Fixed {
+    type Output = Fixed;
+
+    fn add(self, other: &Fixed) -> Fixed {
+        Fixed {
+            mantissa: &self.mantissa + &other.mantissa,
+            bits: self.bits,
+        }
+    }
+}
+
+impl Sub for &Fixed {
+    type Output = Fixed;
+
+    fn sub(self, other: &Fixed) -> Fixed {
+        Fixed {
+            mantissa: &self.mantissa - &other.mantissa,
+            bits: self.bits,
+        }
+    }
+}
+
+impl Mul for &Fixed {
+    type Output = Fixed;
+
+    fn mul(self, other: &Fixed) -> Fixed {
+        Fixed {
+            mantissa: (&self.mantissa * &other.mantissa) >> self.bits,
+            bits: self.bits,
+        }
+    }
+}
+
+pub fn precision_for(pixel_size: f64) -> usize {
+    let needed = -pixel_size.abs().log2().floor();
+    if needed.is_finite() && needed > 0.0 {
+        needed as usize + 64
+    } else {
+        64
+    }
+}
+
+pub fn reference_orbit(center: &(Fixed, Fixed), limit: u32) -> Vec<Complex<f64>> {
+    let (c_re, c_im) = center;
+    let mut re = Fixed::from_f64(0.0, c_re.bits);
+    let mut im = re.clone();
+
+    let mut orbit = vec![Complex { re: 0.0, im: 0.0 }];
+    for _ in 0..limit {
+        let re_sqr = &re * &re;
+        let im_sqr = &im * &im;
+        let re_im = &re * &im;
+        im = &(&re_im + &re_im) + c_im;
+        re = &(&re_sqr - &im_sqr) + c_re;
+
+        let z = Complex {
+            re: re.to_f64(),
+            im: im.to_f64(),
+        };
+        orbit.push(z);
+        if z.norm_sqr() > 4.0 {
+            break;
+        }
+    }
+
+    orbit
+}
+
+pub fn perturbed_escape(
+    orbit: &[Complex<f64>],
+    delta_c: Complex<f64>,
+    limit: u32,
+) -> Option<(u32, f64)> {
+    let mut delta_z = Complex { re: 0.0, im: 0.0 };
+    let mut reference = 0;
+    for i in 0..limit {
+        delta_z = orbit[reference] * delta_z * 2.0 + delta_z * delta_z + delta_c;
+        reference += 1;
+
+        let z = orbit[reference] + delta_z;
+        let norm_sqr = z.norm_sqr();
+        if norm_sqr > 4.0 {
+            return Some((i, norm_sqr));
+        }
+
+        if norm_sqr < delta_z.norm_sqr() || reference == orbit.len() - 1 {
+            delta_z = z;
+            reference = 0;
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fractal::{escape_time, Fractal};
+
+    #[test]
+    fn perturbation_matches_direct_iteration() {
+        let center = (-0.75, 0.1);
+        let reference = (
+            Fixed::parse("-0.75", 128).unwrap(),
+            Fixed::parse("0.1", 128).unwrap(),
+        );
+        let limit = 500;
+        let orbit = reference_orbit(&reference, limit);
+
+        let mut rebased = 0;
+        let steps = 40;
+        for row in 0..steps {
+            for column in 0..steps {
+                let delta = Complex {
+                    re: (column as f64 / steps as f64 - 0.5) * 0.1,
+                    im: (row as f64 / steps as f64 - 0.5) * 0.1,
+                };
+                let point = Complex {
+                    re: center.0 + delta.re,
+                    im: center.1 + delta.im,
+                };
+                let perturbed = perturbed_escape(&orbit, delta, limit).map(|(count, _)| count);
+                assert_eq!(
+                    perturbed,
+                    escape_time(Fractal::Mandelbrot, point, limit),
+                    "{}",
+                    point
+                );
+                if perturbed.is_none_or(|count| count as usize >= orbit.len()) {
+                    rebased += 1;
+                }
+            }
+        }
+        assert!(rebased > 0);
+    }
+
+    #[test]
+    fn parse_accepts_exponents() {
+        let parse = |text| Fixed::parse(text, 96);
+        assert_eq!(parse("-1e-5"), parse("-0.00001"));
+        assert_eq!(parse("2.5E-1"), parse("0.25"));
+        assert_eq!(parse("1.5e+2").unwrap().to_f64(), 150.0);
+        for text in &["1e", "e5", "1e5.5", "1e99999"] {
+            assert_eq!(parse(text), None, "{}", text);
+        }
+
+        let center = parse_center("-0.75,1e-3", 96).unwrap();
+        assert_eq!(center, (parse("-0.75").unwrap(), parse("0.001").unwrap()));
+        assert_eq!(parse_center("-0.75", 96), None);
+        assert_eq!(parse_center("-0.75,i", 96), None);
+    }
+}
@@ -0,0 +1,441 @@
+use super::precision::{complex_from_f64, complex_to_f64, Real};
+use num::{Complex, Zero};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Fractal {
+    Mandelbrot,
+    Julia(Complex<f64>),
+    Multibrot(u32),
+    BurningShip,
+    Tricorn,
+}
+
+impl Fractal {
+    pub fn start<T: Real>(self, point: Complex<T>) -> (Complex<T>, Complex<T>) {
+        match self {
+            Fractal::Julia(c) => (point, complex_from_f64(c)),
+            _ => (Complex::zero(), point),
+        }
+    }
+
+    pub fn step<T: Real>(self, z: Complex<T>, c: Complex<T>) -> Complex<T> {
+        match self {
+            Fractal::Mandelbrot | Fractal::Julia(_) => z * z + c,
+            Fractal::Multibrot(degree) => z.powu(degree) + c,
+            Fractal::BurningShip => {
+                let z = Complex {
+                    re: z.re.abs(),
+                    im: z.im.abs(),
+                };
+                z * z + c
+            }
+            Fractal::Tricorn => z.conj() * z.conj() + c,
+        }
+    }
+
+    pub fn degree(self) -> f64 {
+        match self {
+            Fractal::Multibrot(degree) => degree as f64,
+            _ => 2.0,
+        }
+    }
+
+    pub fn supports_distance(self) -> bool {
+        match self {
+            Fractal::Mandelbrot | Fractal::Julia(_) | Fractal::Multibrot(_) => true,
+            Fractal::BurningShip | Fractal::Tricorn => false,
+        }
+    }
+
+    pub fn in_known_interior<T: Real>(self, point: Complex<T>) -> bool {
+        match self {
+            Fractal::Mandelbrot => {
+                let x = point.re - T::from_f64(0.25);
+                let y_sqr = point.im * point.im;
+                let q = x * x + y_sqr;
+                let in_cardioid = q * (q + x) <= T::from_f64(0.25) * y_sqr;
+                let shifted = point.re + T::one();
+                let in_bulb = shifted * shifted + y_sqr <= T::from_f64(0.0625);
+                in_cardioid || in_bulb
+            }
+            _ => false,
+        }
+    }
+}
+
+pub fn escape_orbit<T: Real>(
+    fractal: Fractal,
+    point: Complex<T>,
+    limit: u32,
+) -> Option<(u32, f64)> {
+    if known_interior(fractal, point) {
+        return None;
+    }
+
+    let bailout = T::from_f64(4.0);
+    let (mut z, c) = fractal.start(point);
+    let mut saved = z;
+    let mut steps = 0u64;
+    let mut window = 8u64;
+    for i in 0..limit {
+        z = fractal.step(z, c);
+        let norm_sqr = z.norm_sqr();
+        if norm_sqr > bailout {
+            return Some((i, norm_sqr.to_f64()));
+        }
+
+        if z == saved {
+            return None;
+        }
+        steps += 1;
+        if steps == window {
+            saved = z;
+            steps = 0;
+            window *= 2;
+        }
+    }
+
+    None
+}
+
+fn known_interior<T: Real>(fractal: Fractal, point: Complex<T>) -> bool {
+    if T::DIGITS > <f64 as Real>::DIGITS {
+        fractal.in_known_interior(point)
+    } else {
+        fractal.in_known_interior(complex_to_f64(point))
+    }
+}
+
+pub fn escape_time<T: Real>(fractal: Fractal, point: Complex<T>, limit: u32) -> Option<u32> {
+    escape_orbit(fractal, point, limit).map(|(count, _)| count)
+}
+
+pub fn plain_escape_time<T: Real>(fractal: Fractal, point: Complex<T>, limit: u32) -> Option<u32> {
+    let bailout = T::from_f64(4.0);
+    let (mut z, c) = fractal.start(point);
+    for i in 0..limit {
+        z = fractal.step(z, c);
+        if z.norm_sqr() > bailout {
+            return Some(i);
+        }
+    }
+
+    None
+}
+
+const DISTANCE_BAILOUT: f64 = 1e10;
+
+pub fn distance_estimate(fractal: Fractal, point: Complex<f64>, limit: u32) -> Option<f64> {
+    let (degree, offset) = match fractal {
+        Fractal::Mandelbrot => (2, 1.0),
+        Fractal::Julia(_) => (2, 0.0),
+        Fractal::Multibrot(degree) => (degree, 1.0),
+        Fractal::BurningShip | Fractal::Tricorn => return None,
+    };
+    if fractal.in_known_interior(point) {
+        return None;
+    }
+
+    let (mut z, c) = fractal.start(point);
+    let mut dz = Complex {
+        re: 1.0 - offset,
+        im: 0.0,
+    };
+    let mut saved = z;
+    let mut steps = 0u64;
+    let mut window = 8u64;
+    for _ in 0..limit {
+        dz = z.powu(degree - 1) * dz * degree as f64 + offset;
+        z = fractal.step(z, c);
+        let norm_sqr = z.norm_sqr();
+        if norm_sqr > DISTANCE_BAILOUT {
+            let norm = norm_sqr.sqrt();
+            return Some(2.0 * norm * norm.ln() / dz.norm());
+        }
+
+        if z == saved {
+            return None;
+        }
+        steps += 1;
+        if steps == window {
+            saved = z;
+            steps = 0;
+            window *= 2;
+        }
+    }
+
+    None
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Coloring {
+    Integer,
+    Smooth,
+    Distance,
+}
+
+impl Coloring {
+    pub fn name(self) -> &'static str {
+        match self {
+            Coloring::Integer => "integer",
+            Coloring::Smooth => "smooth",
+            Coloring::Distance => "distance",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Coloring> {
+        match name {
+            "integer" => Some(Coloring::Integer),
+            "smooth" => Some(Coloring::Smooth),
+            "distance" => Some(Coloring::Distance),
+            _ => None,
+        }
+    }
+}
+
+pub fn orbit_value(fractal: Fractal, orbit: Option<(u32, f64)>, coloring: Coloring) -> Option<f64> {
+    orbit.and_then(|(count, norm_sqr)| match coloring {
+        Coloring::Integer => Some(count as f64),
+        Coloring::Smooth => {
+            let fraction = (0.5 * norm_sqr.ln()).ln() / fractal.degree().ln();
+            Some((count as f64 + 1.0 - fraction).max(0.0))
+        }
+        Coloring::Distance => None,
+    })
+}
+
+pub fn escape_value<T: Real>(
+    fractal: Fractal,
+    point: Complex<T>,
+    limit: u32,
+    coloring: Coloring,
+) -> Option<f64> {
+    match coloring {
+        Coloring::Distance => distance_estimate(fractal, complex_to_f64(point), limit),
+        _ => orbit_value(fractal, escape_orbit(fractal, point, limit), coloring),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_time_counts_steps_until_the_orbit_leaves() {
+        let point = |re, im| Complex { re, im };
+        assert_eq!(
+            escape_time(Fractal::Mandelbrot, point(1.0, 0.0), 255),
+            Some(2)
+        );
+        assert_eq!(
+            escape_time(Fractal::Mandelbrot, point(3.0, 0.0), 255),
+            Some(0)
+        );
+        assert_eq!(escape_time(Fractal::Mandelbrot, point(0.0, 0.0), 255), None);
+        assert_eq!(
+            escape_time(Fractal::Mandelbrot, point(-1.0, 0.0), 255),
+            None
+        );
+        assert_eq!(escape_time(Fractal::Mandelbrot, point(0.26, 0.0), 10), None);
+        assert!(escape_time(Fractal::Mandelbrot, point(0.26, 0.0), 255).is_some());
+    }
+
+    #[test]
+    fn kinds_iterate_their_own_formulas() {
+        let z = Complex { re: -1.0, im: 1.0 };
+        let c = Complex { re: 0.5, im: 0.0 };
+        let step = |fractal: Fractal| fractal.step(z, c);
+        assert_eq!(step(Fractal::Mandelbrot), Complex { re: 0.5, im: -2.0 });
+        assert_eq!(step(Fractal::Multibrot(3)), Complex { re: 2.5, im: 2.0 });
+        assert_eq!(step(Fractal::BurningShip), Complex { re: 0.5, im: 2.0 });
+        assert_eq!(step(Fractal::Tricorn), Complex { re: 0.5, im: 2.0 });
+
+        let julia = Fractal::Julia(Complex {
+            re: -0.8,
+            im: 0.156,
+        });
+        assert_eq!(
+            julia.start(z),
+            (
+                z,
+                Complex {
+                    re: -0.8,
+                    im: 0.156
+                }
+            )
+        );
+        assert_eq!(Fractal::Tricorn.start(z), (Complex::zero(), z));
+
+        let bulb = Complex { re: -1.0, im: 0.0 };
+        assert_eq!(escape_time(Fractal::Mandelbrot, bulb, 500), None);
+        assert_eq!(escape_time(Fractal::Multibrot(3), bulb, 500), Some(2));
+        let point = Complex { re: -0.3, im: 0.6 };
+        let mirrored = Complex { re: -0.3, im: -0.6 };
+        assert_eq!(
+            escape_time(Fractal::Tricorn, point, 500),
+            escape_time(Fractal::Tricorn, mirrored, 500)
+        );
+        assert_ne!(
+            escape_time(Fractal::BurningShip, point, 500),
+            escape_time(Fractal::BurningShip, mirrored, 500)
+        );
+    }
+
+    #[test]
+    fn interior_points_stop_early() {
+        let point = |re, im| Complex { re, im };
+        let mandelbrot = Fractal::Mandelbrot;
+        assert!(mandelbrot.in_known_interior(point(0.2, 0.1)));
+        assert!(mandelbrot.in_known_interior(point(-1.1, 0.1)));
+        assert!(!mandelbrot.in_known_interior(point(-0.1225, 0.7449)));
+        assert!(!mandelbrot.in_known_interior(point(0.3, 0.0)));
+        assert!(!Fractal::Tricorn.in_known_interior(point(0.0, 0.0)));
+
+        for &interior in &[point(0.2, 0.1), point(-1.1, 0.1), point(-0.1225, 0.7449)] {
+            assert_eq!(escape_time(mandelbrot, interior, u32::MAX), None);
+        }
+        let julia = Fractal::Julia(point(-0.12, 0.75));
+        assert_eq!(escape_time(julia, point(0.0, 0.0), u32::MAX), None);
+    }
+
+    #[test]
+    fn escape_time_agrees_with_the_plain_loop() {
+        let fractals = [
+            Fractal::Mandelbrot,
+            Fractal::Julia(Complex {
+                re: -0.8,
+                im: 0.156,
+            }),
+            Fractal::Multibrot(3),
+            Fractal::BurningShip,
+            Fractal::Tricorn,
+        ];
+        for &fractal in &fractals {
+            for row in 0..40 {
+                for column in 0..60 {
+                    let point = Complex {
+                        re: -2.0 + column as f64 * 0.05,
+                        im: -1.0 + row as f64 * 0.05,
+                    };
+                    assert_eq!(
+                        escape_time(fractal, point, 300),
+                        plain_escape_time(fractal, point, 300),
+                        "{:?} at {}",
+                        fractal,
+                        point
+                    );
+                }
+            }
+        }
+    }
+
+    fn estimate(re: f64, im: f64) -> f64 {
+        distance_estimate(Fractal::Mandelbrot, Complex { re, im }, 1000).unwrap()
+    }
+
+    #[test]
+    fn distance_is_within_the_koebe_bounds() {
+        for &(re, im, distance) in &[(1.0, 0.0, 0.75), (-2.5, 0.0, 0.5), (0.25, 2.0, 1.0)] {
+            let estimate = estimate(re, im);
+            assert!(
+                estimate > distance && estimate < distance * 4.0,
+                "{} at {},{}",
+                estimate,
+                re,
+                im
+            );
+        }
+    }
+
+    #[test]
+    fn distance_shrinks_towards_the_boundary() {
+        let distances: Vec<f64> = [0.26, 0.3, 0.5, 1.0]
+            .iter()
+            .map(|&re| estimate(re, 0.0))
+            .collect();
+        assert!(distances.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(distances[0] < 0.05);
+    }
+
+    #[test]
+    fn interior_points_have_no_distance() {
+        let origin = Complex { re: 0.0, im: 0.0 };
+        assert_eq!(distance_estimate(Fractal::Mandelbrot, origin, 1000), None);
+        let period_three = Complex {
+            re: -0.1225,
+            im: 0.7449,
+        };
+        assert_eq!(
+            distance_estimate(Fractal::Mandelbrot, period_three, 1000),
+            None
+        );
+        assert_eq!(distance_estimate(Fractal::BurningShip, origin, 1000), None);
+    }
+
+    #[test]
+    fn interior_shortcut_keeps_double_double_precision() {
+        use crate::fractal::DoubleDouble;
+
+        let edge = Complex { re: -1.25, im: 0.0 };
+        let beyond = Complex {
+            re: DoubleDouble {
+                hi: -1.25,
+                lo: -1e-20,
+            },
+            im: DoubleDouble::default(),
+        };
+        assert!(known_interior(Fractal::Mandelbrot, edge));
+        assert!(known_interior(
+            Fractal::Mandelbrot,
+            Complex {
+                re: -1.25f32,
+                im: 0.0
+            }
+        ));
+        assert!(!known_interior(Fractal::Mandelbrot, beyond));
+    }
+
+    #[test]
+    fn smooth_values_have_no_bands() {
+        let values: Vec<(f64, f64)> = (0..2000)
+            .map(|step| {
+                let point = Complex {
+                    re: 0.3 + step as f64 * 1e-4,
+                    im: 0.0,
+                };
+                let orbit = escape_orbit(Fractal::Mandelbrot, point, 1000);
+                (
+                    orbit_value(Fractal::Mandelbrot, orbit, Coloring::Integer).unwrap(),
+                    orbit_value(Fractal::Mandelbrot, orbit, Coloring::Smooth).unwrap(),
+                )
+            })
+            .collect();
+
+        for pair in values.windows(2) {
+            let ((count, smooth), (next_count, next_smooth)) = (pair[0], pair[1]);
+            assert!(
+                smooth > count && smooth < count + 2.0,
+                "{} for {}",
+                smooth,
+                count
+            );
+            if next_count != count {
+                assert!((next_smooth - smooth).abs() < (next_count - count).abs() / 2.0);
+            }
+        }
+        assert!(values.windows(2).any(|pair| pair[0].0 != pair[1].0));
+    }
+
+    #[test]
+    fn orbits_alone_have_no_distance() {
+        let orbit = Some((5, 100.0));
+        assert_eq!(
+            orbit_value(Fractal::Mandelbrot, orbit, Coloring::Integer),
+            Some(5.0)
+        );
+        assert_eq!(
+            orbit_value(Fractal::Mandelbrot, orbit, Coloring::Distance),
+            None
+        );
+    }
+}
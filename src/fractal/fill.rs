@@ -0,0 +1,221 @@
+use super::{Region, View};
+use num::Complex;
+
+type Rectangle = (usize, usize, usize, usize);
+
+const DIRECT_AREA: usize = 16;
+
+pub fn render_filled<F>(escapes: &mut [Option<f32>], view: View, region: Region, escape: F)
+where
+    F: Fn(&[Complex<f64>], &mut [Option<f32>]),
+{
+    assert!(escapes.len() == region.pixels());
+    if region.pixels() == 0 {
+        return;
+    }
+
+    let compute = |pixels: &[(usize, usize)], escapes: &mut [Option<f32>]| {
+        let points: Vec<Complex<f64>> = pixels
+            .iter()
+            .map(|&(x, y)| {
+                let pixel = (region.left + x, region.top + y);
+                view.pixel_to_point(pixel)
+            })
+            .collect();
+        let mut values = vec![None; points.len()];
+        escape(&points, &mut values);
+        for (&(x, y), value) in pixels.iter().zip(values) {
+            escapes[y * region.width + x] = value;
+        }
+    };
+
+    let whole = (0, 0, region.width, region.height);
+    compute(&border(whole), escapes);
+    fill_rectangle(escapes, region.width, whole, &compute);
+}
+
+fn fill_rectangle<F>(escapes: &mut [Option<f32>], width: usize, rect: Rectangle, compute: &F)
+where
+    F: Fn(&[(usize, usize)], &mut [Option<f32>]),
+{
+    let (left, top, w, h) = rect;
+    if w <= 2 || h <= 2 {
+        return;
+    }
+
+    let first = escapes[top * width + left];
+    if border(rect)
+        .into_iter()
+        .all(|(x, y)| escapes[y * width + x] == first)
+    {
+        for y in top + 1..top + h - 1 {
+            for escape in &mut escapes[y * width + left + 1..y * width + left + w - 1] {
+                *escape = first;
+            }
+        }
+        return;
+    }
+
+    if (w - 2) * (h - 2) <= DIRECT_AREA {
+        let interior: Vec<(usize, usize)> = (top + 1..top + h - 1)
+            .flat_map(|y| (left + 1..left + w - 1).map(move |x| (x, y)))
+            .collect();
+        compute(&interior, escapes);
+        return;
+    }
+
+    if w >= h {
+        let split = left + w / 2;
+        let line: Vec<(usize, usize)> = (top + 1..top + h - 1).map(|y| (split, y)).collect();
+        compute(&line, escapes);
+        fill_rectangle(escapes, width, (left, top, split - left + 1, h), compute);
+        fill_rectangle(escapes, width, (split, top, left + w - split, h), compute);
+    } else {
+        let split = top + h / 2;
+        let line: Vec<(usize, usize)> = (left + 1..left + w - 1).map(|x| (x, split)).collect();
+        compute(&line, escapes);
+        fill_rectangle(escapes, width, (left, top, w, split - top + 1), compute);
+        fill_rectangle(escapes, width, (left, split, w, top + h - split), compute);
+    }
+}
+
+fn border(rect: Rectangle) -> Vec<(usize, usize)> {
+    let (left, top, w, h) = rect;
+    let (right, bottom) = (left + w - 1, top + h - 1);
+    let mut pixels: Vec<(usize, usize)> = (left..=right).map(|x| (x, top)).collect();
+    if h > 1 {
+        pixels.extend((left..=right).map(|x| (x, bottom)));
+    }
+    if w > 1 {
+        for y in top + 1..bottom {
+            pixels.push((left, y));
+            pixels.push((right, y));
+        }
+    } else {
+        pixels.extend((top + 1..bottom).map(|y| (left, y)));
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{render, Coloring, Fractal, Options, Rotation, Sampling};
+    use super::*;
+
+    fn options(coloring: Coloring, limit: u32, fill: bool) -> Options {
+        Options {
+            coloring,
+            limit,
+            fill,
+            ..Options::default()
+        }
+    }
+
+    fn assert_matches_brute_force(
+        fractal: Fractal,
+        bounds: (usize, usize),
+        upper_left: Complex<f64>,
+        lower_right: Complex<f64>,
+        coloring: Coloring,
+        limit: u32,
+    ) {
+        let view = View {
+            bounds,
+            upper_left,
+            lower_right,
+            rotation: Rotation::none(),
+        };
+        let region = Region::full(bounds);
+        let mut expected = vec![None; region.pixels()];
+        let mut actual = vec![None; region.pixels()];
+        render(
+            &mut expected,
+            view,
+            region,
+            fractal,
+            &options(coloring, limit, false),
+        );
+        render(
+            &mut actual,
+            view,
+            region,
+            fractal,
+            &options(coloring, limit, true),
+        );
+        assert!(expected == actual, "{:?} differs from brute force", fractal);
+    }
+
+    #[test]
+    fn border_visits_each_edge_pixel_once() {
+        let mut pixels = border((2, 3, 5, 4));
+        pixels.sort();
+        pixels.dedup();
+        assert_eq!(pixels.len(), 14);
+        assert_eq!(border((0, 0, 1, 3)).len(), 3);
+        assert_eq!(border((0, 0, 4, 1)).len(), 4);
+    }
+
+    #[test]
+    fn full_set_matches_brute_force() {
+        assert_matches_brute_force(
+            Fractal::Mandelbrot,
+            (120, 90),
+            Complex { re: -2.5, im: 1.3 },
+            Complex { re: 1.0, im: -1.3 },
+            Coloring::Integer,
+            255,
+        );
+    }
+
+    #[test]
+    fn cardioid_edge_matches_brute_force() {
+        assert_matches_brute_force(
+            Fractal::Mandelbrot,
+            (100, 80),
+            Complex { re: -0.9, im: 0.4 },
+            Complex { re: -0.5, im: 0.1 },
+            Coloring::Smooth,
+            500,
+        );
+    }
+
+    #[test]
+    fn julia_matches_brute_force() {
+        assert_matches_brute_force(
+            Fractal::Julia(Complex { re: -0.4, im: 0.6 }),
+            (96, 64),
+            Complex { re: -1.6, im: 1.0 },
+            Complex { re: 1.6, im: -1.0 },
+            Coloring::Integer,
+            300,
+        );
+    }
+
+    #[test]
+    fn odd_region_matches_brute_force() {
+        let view = View {
+            bounds: (61, 47),
+            upper_left: Complex { re: -2.0, im: 1.2 },
+            lower_right: Complex { re: 0.5, im: -1.2 },
+            rotation: Rotation::none(),
+        };
+        let region = Region {
+            left: 13,
+            top: 5,
+            width: 37,
+            height: 29,
+        };
+        let escape = |points: &[Complex<f64>], escapes: &mut [Option<f32>]| {
+            for (point, escape) in points.iter().zip(escapes) {
+                *escape = super::super::escape_time(Fractal::Mandelbrot, *point, 100)
+                    .map(|count| count as f32);
+            }
+        };
+
+        let mut expected = vec![None; region.pixels()];
+        super::super::render_samples(&mut expected, view, region, Sampling::Single, escape);
+        let mut actual = vec![None; region.pixels()];
+        render_filled(&mut actual, view, region, escape);
+        assert!(expected == actual);
+    }
+}
@@ -0,0 +1,226 @@
+use super::precision::{complex_from_f64, Real};
+use num::Complex;
+use std::str::FromStr;
+
+pub fn parse_pair<T: FromStr>(s: &str, separator: char) -> Option<(T, T)> {
+    match s.find(separator) {
+        None => None,
+        Some(index) => match (T::from_str(&s[..index]), T::from_str(&s[index + 1..])) {
+            (Ok(l), Ok(r)) => Some((l, r)),
+            _ => None,
+        },
+    }
+}
+
+pub fn parse_complex<T: FromStr>(s: &str) -> Option<Complex<T>> {
+    parse_pair(s, ',').map(|(re, im)| Complex { re, im })
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rotation {
+    pivot: Complex<f64>,
+    turn: Complex<f64>,
+}
+
+impl Rotation {
+    pub fn none() -> Rotation {
+        Rotation {
+            pivot: Complex { re: 0.0, im: 0.0 },
+            turn: Complex { re: 1.0, im: 0.0 },
+        }
+    }
+
+    pub fn new(pivot: Complex<f64>, degrees: f64) -> Rotation {
+        Rotation {
+            pivot,
+            turn: Complex::from_polar(1.0, degrees.to_radians()),
+        }
+    }
+
+    pub fn apply<T: Real>(self, point: Complex<T>) -> Complex<T> {
+        if self.turn.im == 0.0 && self.turn.re == 1.0 {
+            return point;
+        }
+        let pivot = complex_from_f64(self.pivot);
+        pivot + (point - pivot) * complex_from_f64(self.turn)
+    }
+
+    pub fn degrees(self) -> f64 {
+        self.turn.arg().to_degrees()
+    }
+
+    pub fn inverse(self) -> Rotation {
+        Rotation {
+            pivot: self.pivot,
+            turn: self.turn.conj(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct View<T = f64> {
+    pub bounds: (usize, usize),
+    pub upper_left: Complex<T>,
+    pub lower_right: Complex<T>,
+    pub rotation: Rotation,
+}
+
+impl<T: Real> View<T> {
+    pub fn pixel_to_point(&self, pixel: (usize, usize)) -> Complex<T> {
+        self.sample_to_point((pixel.0 as f64 + 0.5, pixel.1 as f64 + 0.5))
+    }
+
+    pub fn sample_to_point(&self, sample: (f64, f64)) -> Complex<T> {
+        let (upper_left, lower_right) = (self.upper_left, self.lower_right);
+        let (width, height) = (
+            lower_right.re - upper_left.re,
+            upper_left.im - lower_right.im,
+        );
+        self.rotation.apply(Complex {
+            re: upper_left.re + T::from_f64(sample.0 / self.bounds.0 as f64) * width,
+            im: upper_left.im - T::from_f64(sample.1 / self.bounds.1 as f64) * height,
+        })
+    }
+
+    pub fn pixel_size(&self) -> f64 {
+        (self.lower_right.re - self.upper_left.re).to_f64().abs() / self.bounds.0 as f64
+    }
+}
+
+impl View {
+    pub fn point_to_pixel(&self, point: Complex<f64>) -> Option<(usize, usize)> {
+        let (upper_left, lower_right) = (self.upper_left, self.lower_right);
+        let point = self.rotation.inverse().apply(point);
+        let (width, height) = (
+            lower_right.re - upper_left.re,
+            upper_left.im - lower_right.im,
+        );
+        let column = (point.re - upper_left.re) / width * self.bounds.0 as f64;
+        let row = (upper_left.im - point.im) / height * self.bounds.1 as f64;
+        let (columns, rows) = (self.bounds.0 as f64, self.bounds.1 as f64);
+        if column >= 0.0 && row >= 0.0 && column < columns && row < rows {
+            Some((column as usize, row as usize))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub left: usize,
+    pub top: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    pub fn full(bounds: (usize, usize)) -> Region {
+        Region {
+            left: 0,
+            top: 0,
+            width: bounds.0,
+            height: bounds.1,
+        }
+    }
+
+    pub fn pixels(&self) -> usize {
+        self.width * self.height
+    }
+}
+
+pub fn frame_corners<T: Real>(
+    target: Complex<T>,
+    width: f64,
+    bounds: (usize, usize),
+) -> (Complex<T>, Complex<T>) {
+    let height = width * bounds.1 as f64 / bounds.0 as f64;
+    let (half_width, half_height) = (T::from_f64(width / 2.0), T::from_f64(height / 2.0));
+    let upper_left = Complex {
+        re: target.re - half_width,
+        im: target.im + half_height,
+    };
+    let lower_right = Complex {
+        re: target.re + half_width,
+        im: target.im - half_height,
+    };
+    (upper_left, lower_right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pair_splits_on_the_separator() {
+        assert_eq!(parse_pair::<i32>("", ','), None);
+        assert_eq!(parse_pair::<i32>("10,", ','), None);
+        assert_eq!(parse_pair::<i32>(",10", ','), None);
+        assert_eq!(parse_pair::<i32>("10,20", ','), Some((10, 20)));
+        assert_eq!(parse_pair::<i32>("10,20xy", ','), None);
+        assert_eq!(parse_pair::<f64>("0.5x", 'x'), None);
+        assert_eq!(parse_pair::<f64>("0.5x1.5", 'x'), Some((0.5, 1.5)));
+    }
+
+    #[test]
+    fn parse_complex_reads_re_and_im() {
+        assert_eq!(
+            parse_complex("1.25,-0.0625"),
+            Some(Complex {
+                re: 1.25,
+                im: -0.0625
+            })
+        );
+        assert_eq!(parse_complex::<f64>(",-0.0625"), None);
+    }
+
+    #[test]
+    fn pixel_to_point_maps_corners_and_rotates() {
+        let view = View {
+            bounds: (100, 200),
+            upper_left: Complex { re: -1.0, im: 1.0 },
+            lower_right: Complex { re: 1.0, im: -1.0 },
+            rotation: Rotation::none(),
+        };
+        assert_eq!(
+            view.pixel_to_point((37, 87)),
+            Complex {
+                re: -0.25,
+                im: 0.125
+            }
+        );
+
+        let quarter = Rotation::new(Complex { re: 0.0, im: 0.0 }, 90.0);
+        let point = View {
+            rotation: quarter,
+            ..view
+        }
+        .pixel_to_point((99, 99));
+        assert!(
+            (point
+                - Complex {
+                    re: -0.005,
+                    im: 0.99
+                })
+            .norm()
+                < 1e-12
+        );
+        assert!(
+            (quarter.inverse().apply(point)
+                - Complex {
+                    re: 0.99,
+                    im: 0.005
+                })
+            .norm()
+                < 1e-12
+        );
+    }
+
+    #[test]
+    fn frame_corners_follow_the_aspect_ratio() {
+        let (upper_left, lower_right) =
+            frame_corners(Complex { re: -0.75, im: 0.0 }, 4.0, (800, 600));
+        assert_eq!(upper_left, Complex { re: -2.75, im: 1.5 });
+        assert_eq!(lower_right, Complex { re: 1.25, im: -1.5 });
+    }
+}
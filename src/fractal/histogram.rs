@@ -0,0 +1,108 @@
+use super::palette::Palette;
+use super::{Coloring, Options};
+use std::borrow::Cow;
+
+pub fn equalize(escapes: &[Option<f32>]) -> Vec<Option<f32>> {
+    let mut sorted: Vec<f32> = escapes.iter().flatten().cloned().collect();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let total = sorted.len() as f32;
+
+    escapes
+        .iter()
+        .map(|escape| {
+            escape.map(|value| {
+                let rank = sorted.partition_point(|&other| other <= value);
+                rank as f32 / total
+            })
+        })
+        .collect()
+}
+
+pub fn equalize_for(escapes: &[Option<f32>], palette: &Palette) -> Vec<Option<f32>> {
+    let scale = palette.cycle().unwrap_or(1) as f32;
+    equalize(escapes)
+        .into_iter()
+        .map(|escape| escape.map(|value| value * scale))
+        .collect()
+}
+
+pub fn shaded_for<'a>(
+    escapes: &'a [Option<f32>],
+    palette: &Palette,
+    options: &Options,
+) -> Cow<'a, [Option<f32>]> {
+    match palette.cycle() {
+        _ if options.equalize => Cow::Owned(equalize_for(escapes, palette)),
+        Some(period) if options.coloring == Coloring::Distance => Cow::Owned(
+            escapes
+                .iter()
+                .map(|escape| escape.map(|value| value * period as f32))
+                .collect(),
+        ),
+        _ => Cow::Borrowed(escapes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equalized_values_follow_the_distribution() {
+        let escapes = [Some(1.0), Some(1.0), Some(1.0), Some(50.0), None, Some(2.0)];
+        assert_eq!(
+            equalize(&escapes),
+            vec![Some(0.6), Some(0.6), Some(0.6), Some(1.0), None, Some(0.8)]
+        );
+    }
+
+    #[test]
+    fn equalized_values_span_one_palette_cycle() {
+        let escapes: Vec<Option<f32>> = (0..100).map(|count| Some(count as f32)).collect();
+        let gradient = equalize_for(&escapes, &Palette::builtin("fire").unwrap());
+        assert_eq!(gradient[99], Some(1.0));
+
+        let cyclic = equalize_for(&escapes, &Palette::builtin("ultra").unwrap());
+        assert_eq!(cyclic[49], Some(32.0));
+        assert_eq!(cyclic[99], Some(64.0));
+    }
+
+    #[test]
+    fn distances_span_one_palette_cycle() {
+        let distances = [Some(0.25), None, Some(1.0)];
+        let options = Options {
+            coloring: Coloring::Distance,
+            ..Options::default()
+        };
+        let cyclic = Palette::builtin("ultra").unwrap();
+        assert_eq!(
+            shaded_for(&distances, &cyclic, &options).to_vec(),
+            vec![Some(16.0), None, Some(64.0)]
+        );
+        let gradient = Palette::builtin("fire").unwrap();
+        assert_eq!(
+            shaded_for(&distances, &gradient, &options).to_vec(),
+            distances
+        );
+    }
+
+    #[test]
+    fn equalized_gray_spreads_skewed_counts() {
+        let mut escapes: Vec<Option<f32>> = (0..90).map(|i| Some((3 + i % 10) as f32)).collect();
+        escapes.extend((1..=10).map(|i| Some((i * 100) as f32)));
+        let palette = Palette::builtin("gray").unwrap();
+        let distinct = |escapes: &[Option<f32>], range: (f64, f64)| {
+            let mut shades: Vec<u8> = escapes[..90]
+                .iter()
+                .map(|escape| palette.color(escape.map(f64::from), range)[0])
+                .collect();
+            shades.sort_unstable();
+            shades.dedup();
+            shades.len()
+        };
+
+        assert!(distinct(&escapes, (3.0, 1000.0)) <= 3);
+        let equalized = equalize(&escapes);
+        assert_eq!(distinct(&equalized, (0.09, 1.0)), 10);
+    }
+}
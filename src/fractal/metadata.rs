@@ -0,0 +1,348 @@
+use super::{
+    parse_center, parse_complex, parse_pair, Center, Coloring, DoubleDouble, Format, Fractal,
+    Options, Precision, RenderRequest, Rotation,
+};
+use std::fs;
+use std::io;
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const IHDR_END: usize = 8 + 4 + 4 + 13 + 4;
+const SOFTWARE: &str = concat!("mandelbrot ", env!("CARGO_PKG_VERSION"));
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn invalid(filename: &str, reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: {}", filename, reason),
+    )
+}
+
+pub fn embed_text(filename: &str, text: &[(String, String)]) -> Result<(), io::Error> {
+    let png = fs::read(filename)?;
+    if !png.starts_with(SIGNATURE) || png.get(12..16) != Some(b"IHDR") || png.len() < IHDR_END {
+        return Err(invalid(filename, "not a png file"));
+    }
+
+    let mut output = png[..IHDR_END].to_vec();
+    for (keyword, value) in text {
+        let mut chunk = b"tEXt".to_vec();
+        chunk.extend_from_slice(keyword.as_bytes());
+        chunk.push(0);
+        chunk.extend_from_slice(value.as_bytes());
+        output.extend_from_slice(&(chunk.len() as u32 - 4).to_be_bytes());
+        output.extend_from_slice(&chunk);
+        output.extend_from_slice(&crc32(&chunk).to_be_bytes());
+    }
+    output.extend_from_slice(&png[IHDR_END..]);
+    fs::write(filename, output)
+}
+
+pub fn read_text(filename: &str) -> Result<Vec<(String, String)>, io::Error> {
+    let png = fs::read(filename)?;
+    if !png.starts_with(SIGNATURE) {
+        return Err(invalid(filename, "not a png file"));
+    }
+
+    let mut text = Vec::new();
+    let mut offset = SIGNATURE.len();
+    while offset + 8 <= png.len() {
+        let mut length = [0; 4];
+        length.copy_from_slice(&png[offset..offset + 4]);
+        let length = u32::from_be_bytes(length) as usize;
+        let kind = &png[offset + 4..offset + 8];
+        let data = png
+            .get(offset + 8..offset + 8 + length)
+            .ok_or_else(|| invalid(filename, "truncated png chunk"))?;
+        if kind == b"IEND" {
+            break;
+        }
+        if kind == b"tEXt" {
+            if let Some(split) = data.iter().position(|&byte| byte == 0) {
+                text.push((
+                    String::from_utf8_lossy(&data[..split]).into_owned(),
+                    String::from_utf8_lossy(&data[split + 1..]).into_owned(),
+                ));
+            }
+        }
+        offset += 12 + length;
+    }
+    Ok(text)
+}
+
+fn fractal_text(fractal: Fractal) -> String {
+    match fractal {
+        Fractal::Mandelbrot => "mandelbrot".to_string(),
+        Fractal::Julia(c) => format!("julia {},{}", c.re, c.im),
+        Fractal::Multibrot(degree) => format!("multibrot {}", degree),
+        Fractal::BurningShip => "burning-ship".to_string(),
+        Fractal::Tricorn => "tricorn".to_string(),
+    }
+}
+
+fn parse_fractal(text: &str) -> Option<Fractal> {
+    let (kind, parameter) = match text.find(' ') {
+        Some(index) => (&text[..index], Some(&text[index + 1..])),
+        None => (text, None),
+    };
+    match (kind, parameter) {
+        ("mandelbrot", None) => Some(Fractal::Mandelbrot),
+        ("julia", Some(c)) => parse_complex(c).map(Fractal::Julia),
+        ("multibrot", Some(degree)) => degree.parse().ok().map(Fractal::Multibrot),
+        ("burning-ship", None) => Some(Fractal::BurningShip),
+        ("tricorn", None) => Some(Fractal::Tricorn),
+        _ => None,
+    }
+}
+
+pub fn request_text(request: &RenderRequest) -> Vec<(String, String)> {
+    let degrees = (request.rotation.degrees() * 1e9).round() / 1e9;
+    let options = &request.options;
+    let mut text = vec![
+        ("Software", SOFTWARE.to_string()),
+        ("Fractal", fractal_text(request.fractal)),
+        ("Size", format!("{}x{}", request.bounds.0, request.bounds.1)),
+        (
+            "Upper-Left",
+            format!("{},{}", request.upper_left.re, request.upper_left.im),
+        ),
+        (
+            "Lower-Right",
+            format!("{},{}", request.lower_right.re, request.lower_right.im),
+        ),
+        ("Rotation", format!("{}", degrees)),
+        ("Iterations", options.limit.to_string()),
+        ("Coloring", options.coloring.name().to_string()),
+        ("Palette", options.palette.clone()),
+        ("Equalize", options.equalize.to_string()),
+        ("Precision", request.precision.name().to_string()),
+    ];
+    if let Some(center) = &request.center {
+        text.push(("Center", center.text.clone()));
+        text.push(("Width", center.width.to_string()));
+    }
+    text.into_iter()
+        .map(|(keyword, value)| (keyword.to_string(), value))
+        .collect()
+}
+
+pub fn request_from_text(text: &[(String, String)]) -> Result<RenderRequest, String> {
+    let field = |keyword: &str| {
+        text.iter()
+            .find(|(key, _)| key == keyword)
+            .map(|(_, value)| value.as_str())
+    };
+    let required = |keyword: &str| {
+        field(keyword).ok_or_else(|| format!("the image holds no `{}` render parameter", keyword))
+    };
+    let malformed =
+        |keyword: &str, value: &str| format!("malformed `{}` parameter `{}`", keyword, value);
+
+    let parse = |keyword: &'static str| -> Result<_, String> {
+        let value = required(keyword)?;
+        parse_complex(value).ok_or_else(|| malformed(keyword, value))
+    };
+    let upper_left = parse("Upper-Left")?;
+    let lower_right = parse("Lower-Right")?;
+
+    let value = required("Fractal")?;
+    let fractal = parse_fractal(value).ok_or_else(|| malformed("Fractal", value))?;
+    let value = required("Size")?;
+    let bounds = parse_pair(value, 'x')
+        .filter(|&(width, height)| width > 0 && height > 0)
+        .ok_or_else(|| malformed("Size", value))?;
+    let value = required("Iterations")?;
+    let limit = value
+        .parse()
+        .ok()
+        .filter(|&limit| limit > 0)
+        .ok_or_else(|| malformed("Iterations", value))?;
+
+    let mut options = Options {
+        limit,
+        ..Options::default()
+    };
+    if let Some(value) = field("Coloring") {
+        options.coloring =
+            Coloring::from_name(value).ok_or_else(|| malformed("Coloring", value))?;
+    }
+    if let Some(value) = field("Palette") {
+        options.palette = value.to_string();
+    }
+    if let Some(value) = field("Equalize") {
+        options.equalize = value.parse().map_err(|_| malformed("Equalize", value))?;
+    }
+    let degrees = match field("Rotation") {
+        Some(value) => value.parse().map_err(|_| malformed("Rotation", value))?,
+        None => 0.0,
+    };
+    let precision = match field("Precision") {
+        Some(value) => Precision::from_name(value).ok_or_else(|| malformed("Precision", value))?,
+        None => Precision::Double,
+    };
+    let center = match field("Center") {
+        Some(value) => {
+            let parses = match precision {
+                Precision::Deep => parse_center(value, 64).is_some(),
+                _ => parse_complex::<DoubleDouble>(value).is_some(),
+            };
+            if !parses {
+                return Err(malformed("Center", value));
+            }
+            let width = required("Width")?;
+            Some(Center {
+                text: value.to_string(),
+                width: width.parse().map_err(|_| malformed("Width", width))?,
+            })
+        }
+        None => None,
+    };
+
+    Ok(RenderRequest {
+        fractal,
+        bounds,
+        upper_left,
+        lower_right,
+        rotation: Rotation::new((upper_left + lower_right) / 2.0, degrees),
+        precision,
+        center,
+        options,
+    })
+}
+
+pub fn write_metadata(
+    filename: &str,
+    format: Format,
+    request: &RenderRequest,
+) -> Result<(), io::Error> {
+    match format {
+        Format::Png | Format::Png16 => embed_text(filename, &request_text(request)),
+        _ => Ok(()),
+    }
+}
+
+pub fn read_metadata(filename: &str) -> Result<RenderRequest, io::Error> {
+    request_from_text(&read_text(filename)?).map_err(|reason| invalid(filename, &reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num::Complex;
+
+    #[test]
+    fn crc32_matches_the_png_reference() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn render_parameters_survive_a_png_round_trip() {
+        let filename = std::env::temp_dir().join(format!("metadata-{}.png", std::process::id()));
+        let filename = filename.to_str().unwrap();
+
+        let upper_left = Complex { re: -1.25, im: 0.4 };
+        let lower_right = Complex { re: -0.95, im: 0.2 };
+        let mut request = RenderRequest::new((30, 20), upper_left, lower_right);
+        request.fractal = Fractal::Julia(Complex {
+            re: -0.8,
+            im: 0.156,
+        });
+        request.rotation = Rotation::new((upper_left + lower_right) / 2.0, 30.0);
+        request.options.limit = 500;
+        request.options.coloring = Coloring::Smooth;
+        request.options.palette = "fire".to_string();
+
+        request.render().unwrap().save(filename).unwrap();
+        write_metadata(filename, Format::Png, &request).unwrap();
+        let pixels = image::open(filename).unwrap().raw_pixels();
+        let restored = read_metadata(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(format!("{:?}", restored), format!("{:?}", request));
+        assert_eq!(pixels, request.render().unwrap().pixels);
+    }
+
+    #[test]
+    fn precision_and_center_survive_the_text_round_trip() {
+        let center = Complex { re: -2.0, im: 0.0 };
+        let mut request = RenderRequest::new((60, 45), center, center);
+        request.rotation = Rotation::new(center, 30.0);
+        request.precision = Precision::DoubleDouble;
+        request.center = Some(Center {
+            text: "-1.99999999999999999,0".to_string(),
+            width: 1e-18,
+        });
+
+        let restored = request_from_text(&request_text(&request)).unwrap();
+        assert_eq!(format!("{:?}", restored), format!("{:?}", request));
+        assert_eq!(restored.escapes().unwrap(), request.escapes().unwrap());
+    }
+
+    #[test]
+    fn deep_centers_survive_the_text_round_trip() {
+        let center = Complex { re: -0.75, im: 0.1 };
+        let mut request = RenderRequest::new((24, 18), center, center);
+        request.rotation = Rotation::new(center, 0.0);
+        request.precision = Precision::Deep;
+        request.center = Some(Center {
+            text: "-7.5e-1,0.1000000000000000000000000001".to_string(),
+            width: 1e-3,
+        });
+
+        let restored = request_from_text(&request_text(&request)).unwrap();
+        assert_eq!(format!("{:?}", restored), format!("{:?}", request));
+        assert_eq!(restored.escapes().unwrap(), request.escapes().unwrap());
+    }
+
+    #[test]
+    fn missing_parameters_are_reported() {
+        let text = vec![("Software".to_string(), SOFTWARE.to_string())];
+        assert_eq!(
+            request_from_text(&text).unwrap_err(),
+            "the image holds no `Upper-Left` render parameter"
+        );
+    }
+
+    #[test]
+    fn empty_sizes_and_zero_limits_are_rejected() {
+        let request = RenderRequest::new(
+            (30, 20),
+            Complex { re: -2.0, im: 1.0 },
+            Complex { re: 1.0, im: -1.0 },
+        );
+        let with = |keyword: &str, value: &str| {
+            let mut text = request_text(&request);
+            for entry in text.iter_mut().filter(|(key, _)| key == keyword) {
+                entry.1 = value.to_string();
+            }
+            request_from_text(&text).err()
+        };
+        assert_eq!(
+            with("Size", "0x10"),
+            Some("malformed `Size` parameter `0x10`".to_string())
+        );
+        assert_eq!(
+            with("Size", "10x0"),
+            Some("malformed `Size` parameter `10x0`".to_string())
+        );
+        assert_eq!(
+            with("Iterations", "0"),
+            Some("malformed `Iterations` parameter `0`".to_string())
+        );
+        assert_eq!(with("Iterations", "1"), None);
+    }
+}
@@ -0,0 +1,45 @@
+pub mod deep;
+pub mod fill;
+pub mod palette;
+pub mod simd;
+
+mod buddhabrot;
+mod escape;
+mod geometry;
+mod histogram;
+mod metadata;
+mod output;
+mod precision;
+mod render;
+mod request;
+mod sampling;
+mod scene;
+mod schedule;
+mod terminal;
+
+pub use self::buddhabrot::{density_pixels, density_values, render_density, Density};
+pub use self::deep::parse_center;
+pub use self::escape::{
+    distance_estimate, escape_orbit, escape_time, escape_value, orbit_value, plain_escape_time,
+    Coloring, Fractal,
+};
+pub use self::geometry::{frame_corners, parse_complex, parse_pair, Region, Rotation, View};
+pub use self::histogram::{equalize, equalize_for, shaded_for};
+pub use self::metadata::{
+    embed_text, read_metadata, read_text, request_from_text, request_text, write_metadata,
+};
+pub use self::output::{
+    pixel_counts, read_dump, write_dump, write_escapes, write_image, Dump, Format,
+};
+pub use self::precision::{
+    complex_from_f64, complex_to_f64, DoubleDouble, ParseDoubleDoubleError, Precision, Real,
+};
+pub use self::render::{
+    colorize, colorize_wide, deep_values, escape_range, refine_adaptive, render, render_deep,
+    render_layers, render_precise, render_samples, render_view, Options,
+};
+pub use self::request::{Center, ImageBuffer, RenderRequest};
+pub use self::sampling::Sampling;
+pub use self::scene::{parse_scenes, read_scenes, Scene};
+pub use self::schedule::TileScheduler;
+pub use self::terminal::{ansi256, half_blocks, ColorDepth};
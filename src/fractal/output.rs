@@ -0,0 +1,495 @@
+use super::palette::Palette;
+use super::{colorize_wide, escape_range, shaded_for, Coloring, ImageBuffer, Options};
+use image::png::PNGEncoder;
+use image::ColorType;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    Png,
+    Png16,
+    Ppm,
+    Pgm,
+    Tga,
+    Raw,
+    Dump,
+}
+
+const DUMP_MAGIC: &[u8; 8] = b"MBDUMP1\n";
+const INTERIOR_BITS: u32 = 0x7fc0_0000;
+const MAX_DUMP_ESCAPES: usize = u32::MAX as usize;
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "png" => Some(Format::Png),
+            "png16" => Some(Format::Png16),
+            "ppm" => Some(Format::Ppm),
+            "pgm" => Some(Format::Pgm),
+            "tga" => Some(Format::Tga),
+            "raw" => Some(Format::Raw),
+            "dump" => Some(Format::Dump),
+            _ => None,
+        }
+    }
+
+    pub fn from_extension(filename: &str) -> Option<Format> {
+        let extension = Path::new(filename).extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(Format::Png),
+            "ppm" => Some(Format::Ppm),
+            "pgm" => Some(Format::Pgm),
+            "tga" => Some(Format::Tga),
+            "raw" => Some(Format::Raw),
+            "dump" => Some(Format::Dump),
+            _ => None,
+        }
+    }
+}
+
+fn create(filename: &str) -> Result<BufWriter<File>, io::Error> {
+    File::create(filename)
+        .map(BufWriter::new)
+        .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", filename, error)))
+}
+
+fn open(filename: &str) -> Result<BufReader<File>, io::Error> {
+    File::open(filename)
+        .map(BufReader::new)
+        .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", filename, error)))
+}
+
+pub fn write_image(
+    filename: &str,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    color_type: ColorType,
+) -> Result<(), io::Error> {
+    let output = create(filename)?;
+
+    let encoder = PNGEncoder::new(output);
+    encoder.encode(pixels, bounds.0 as u32, bounds.1 as u32, color_type)?;
+
+    Ok(())
+}
+
+pub fn write_escapes(
+    filename: &str,
+    escapes: &[Option<f32>],
+    bounds: (usize, usize),
+    palette: &Palette,
+    format: Format,
+    options: &Options,
+    range: Option<(f64, f64)>,
+) -> Result<(), io::Error> {
+    let shaded = shaded_for(escapes, palette, options);
+    let range = range.unwrap_or_else(|| escape_range(&shaded));
+
+    match format {
+        Format::Png => ImageBuffer::from_escapes_in(&shaded, bounds, palette, range).save(filename),
+        Format::Png16 => write_wide_png(filename, &shaded, bounds, palette, range),
+        Format::Ppm | Format::Pgm | Format::Tga => {
+            let image = ImageBuffer::from_escapes_in(&shaded, bounds, palette, range);
+            let channels = palette.channels();
+            let mut output = create(filename)?;
+            match format {
+                Format::Ppm => {
+                    write!(output, "P6\n{} {}\n255\n", bounds.0, bounds.1)?;
+                    for pixel in image.pixels.chunks(channels) {
+                        output.write_all(&pixel[..3])?;
+                    }
+                }
+                Format::Pgm => {
+                    write!(output, "P5\n{} {}\n255\n", bounds.0, bounds.1)?;
+                    for pixel in image.pixels.chunks(channels) {
+                        output.write_all(&[luma(pixel)])?;
+                    }
+                }
+                _ => write_tga(&mut output, &image.pixels, bounds, channels)?,
+            }
+            output.flush()
+        }
+        Format::Raw => {
+            if options.coloring != Coloring::Integer {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "raw files hold integer escape counts; render with integer coloring",
+                ));
+            }
+            let mut output = create(filename)?;
+            for count in pixel_counts(escapes, bounds) {
+                output.write_all(&count.to_le_bytes())?;
+            }
+            output.flush()
+        }
+        Format::Dump => {
+            let mut dump = Dump::new(bounds, escapes.len() / (bounds.0 * bounds.1));
+            dump.layers.push((options.coloring, escapes.to_vec()));
+            write_dump(filename, &dump)
+        }
+    }
+}
+
+fn write_wide_png(
+    filename: &str,
+    escapes: &[Option<f32>],
+    bounds: (usize, usize),
+    palette: &Palette,
+    range: (f64, f64),
+) -> Result<(), io::Error> {
+    let mut pixels = vec![0; bounds.0 * bounds.1 * palette.channels()];
+    colorize_wide(&mut pixels, escapes, palette, range);
+
+    let bytes: Vec<u8> = pixels
+        .iter()
+        .flat_map(|channel| channel.to_be_bytes().to_vec())
+        .collect();
+    write_image(filename, &bytes, bounds, palette.wide_color_type())
+}
+
+fn luma(pixel: &[u8]) -> u8 {
+    let (r, g, b) = (pixel[0] as f64, pixel[1] as f64, pixel[2] as f64);
+    (0.2126 * r + 0.7152 * g + 0.0722 * b).round() as u8
+}
+
+fn write_tga<W: Write>(
+    output: &mut W,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    channels: usize,
+) -> Result<(), io::Error> {
+    if bounds.0 > u16::MAX as usize || bounds.1 > u16::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "TGA images are limited to 65535 pixels per side",
+        ));
+    }
+
+    let mut header = [0u8; 18];
+    header[2] = 2;
+    header[12..14].copy_from_slice(&(bounds.0 as u16).to_le_bytes());
+    header[14..16].copy_from_slice(&(bounds.1 as u16).to_le_bytes());
+    header[16] = (channels * 8) as u8;
+    header[17] = 0x20 | if channels == 4 { 8 } else { 0 };
+    output.write_all(&header)?;
+
+    for pixel in pixels.chunks(channels) {
+        output.write_all(&[pixel[2], pixel[1], pixel[0]])?;
+        if channels == 4 {
+            output.write_all(&[pixel[3]])?;
+        }
+    }
+    Ok(())
+}
+
+pub fn pixel_counts(escapes: &[Option<f32>], bounds: (usize, usize)) -> Vec<u32> {
+    let samples = escapes.len() / (bounds.0 * bounds.1);
+    escapes
+        .chunks(samples)
+        .map(|pixel| {
+            let escaped: Vec<f32> = pixel.iter().flatten().cloned().collect();
+            if escaped.is_empty() {
+                u32::MAX
+            } else {
+                (escaped.iter().sum::<f32>() / escaped.len() as f32) as u32
+            }
+        })
+        .collect()
+}
+
+pub struct Dump {
+    pub bounds: (usize, usize),
+    pub samples: usize,
+    pub layers: Vec<(Coloring, Vec<Option<f32>>)>,
+}
+
+impl Dump {
+    pub fn new(bounds: (usize, usize), samples: usize) -> Dump {
+        Dump {
+            bounds,
+            samples,
+            layers: Vec::new(),
+        }
+    }
+
+    pub fn layer(&self, coloring: Coloring) -> Option<&[Option<f32>]> {
+        self.layers
+            .iter()
+            .find(|(layer, _)| *layer == coloring)
+            .map(|(_, escapes)| &escapes[..])
+    }
+}
+
+pub fn write_dump(filename: &str, dump: &Dump) -> Result<(), io::Error> {
+    let mut output = create(filename)?;
+    write_layers(&mut output, dump)?;
+    output.flush()
+}
+
+fn write_layers<W: Write>(output: &mut W, dump: &Dump) -> Result<(), io::Error> {
+    output.write_all(DUMP_MAGIC)?;
+    for &value in &[
+        dump.bounds.0,
+        dump.bounds.1,
+        dump.samples,
+        dump.layers.len(),
+    ] {
+        output.write_all(&(value as u32).to_le_bytes())?;
+    }
+
+    for (coloring, escapes) in &dump.layers {
+        assert!(escapes.len() == dump.bounds.0 * dump.bounds.1 * dump.samples);
+        let tag = match coloring {
+            Coloring::Integer => 0,
+            Coloring::Smooth => 1,
+            Coloring::Distance => 2,
+        };
+        output.write_all(&[tag])?;
+        write_escape_runs(output, escapes)?;
+    }
+    Ok(())
+}
+
+fn write_escape_runs<W: Write>(output: &mut W, escapes: &[Option<f32>]) -> Result<(), io::Error> {
+    let bits: Vec<u32> = escapes
+        .iter()
+        .map(|escape| escape.map_or(INTERIOR_BITS, f32::to_bits))
+        .collect();
+    let run_at = |index: usize| {
+        bits[index..]
+            .iter()
+            .take_while(|&&value| value == bits[index])
+            .count()
+    };
+
+    let mut index = 0;
+    while index < bits.len() {
+        let run = run_at(index);
+        if run > 1 {
+            write_varint(output, (run as u64) << 1)?;
+            output.write_all(&bits[index].to_le_bytes())?;
+            index += run;
+            continue;
+        }
+
+        let start = index;
+        while index < bits.len() && run_at(index) == 1 {
+            index += 1;
+        }
+        write_varint(output, ((index - start) as u64) << 1 | 1)?;
+        for value in &bits[start..index] {
+            output.write_all(&value.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+pub fn read_dump(filename: &str) -> Result<Dump, io::Error> {
+    read_layers(&mut open(filename)?).map_err(|error| {
+        let kind = match error.kind() {
+            io::ErrorKind::UnexpectedEof => io::ErrorKind::InvalidData,
+            kind => kind,
+        };
+        io::Error::new(kind, format!("{}: {}", filename, error))
+    })
+}
+
+fn read_layers<R: Read>(input: &mut R) -> Result<Dump, io::Error> {
+    let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, reason.to_string());
+
+    let mut magic = [0; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != DUMP_MAGIC {
+        return Err(invalid("not an iteration dump"));
+    }
+    let mut header = [0; 16];
+    input.read_exact(&mut header)?;
+    let field = |index: usize| {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&header[index * 4..index * 4 + 4]);
+        u32::from_le_bytes(bytes) as usize
+    };
+
+    let (width, height, samples) = (field(0), field(1), field(2));
+    if width == 0 || height == 0 || samples == 0 {
+        return Err(invalid("empty image"));
+    }
+    let total = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(samples))
+        .filter(|&total| total <= MAX_DUMP_ESCAPES)
+        .ok_or_else(|| invalid("image is too large"))?;
+    let mut dump = Dump::new((width, height), samples);
+    for _ in 0..field(3) {
+        let mut tag = [0];
+        input.read_exact(&mut tag)?;
+        let coloring = match tag[0] {
+            0 => Coloring::Integer,
+            1 => Coloring::Smooth,
+            2 => Coloring::Distance,
+            _ => return Err(invalid("unknown layer")),
+        };
+        dump.layers
+            .push((coloring, read_escape_runs(input, total)?));
+    }
+    Ok(dump)
+}
+
+fn read_escape_runs<R: Read>(input: &mut R, total: usize) -> Result<Vec<Option<f32>>, io::Error> {
+    let read_escape = |input: &mut R| -> Result<Option<f32>, io::Error> {
+        let mut bytes = [0; 4];
+        input.read_exact(&mut bytes)?;
+        let value = u32::from_le_bytes(bytes);
+        Ok(if value == INTERIOR_BITS {
+            None
+        } else {
+            Some(f32::from_bits(value))
+        })
+    };
+
+    let mut escapes = Vec::new();
+    while escapes.len() < total {
+        let header = read_varint(input)?;
+        let length = (header >> 1) as usize;
+        if length == 0 || escapes.len() + length > total {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "corrupt run length",
+            ));
+        }
+        if header & 1 == 0 {
+            let escape = read_escape(input)?;
+            escapes.extend(std::iter::repeat_n(escape, length));
+        } else {
+            for _ in 0..length {
+                escapes.push(read_escape(input)?);
+            }
+        }
+    }
+    Ok(escapes)
+}
+
+fn write_varint<W: Write>(output: &mut W, mut value: u64) -> Result<(), io::Error> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return output.write_all(&[byte]);
+        }
+        output.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint<R: Read>(input: &mut R) -> Result<u64, io::Error> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        input.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint is too long",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_round_trips() {
+        let mut escapes = vec![None; 30];
+        for (index, escape) in escapes.iter_mut().enumerate().skip(12) {
+            *escape = Some((index / 5) as f32 + 0.25);
+        }
+        escapes[27] = Some(0.5);
+        escapes[29] = Some(0.0);
+        let counts: Vec<Option<f32>> = escapes
+            .iter()
+            .map(|escape| escape.map(f32::floor))
+            .collect();
+
+        let mut dump = Dump::new((5, 3), 2);
+        dump.layers.push((Coloring::Integer, counts.clone()));
+        dump.layers.push((Coloring::Smooth, escapes.clone()));
+        let mut bytes = Vec::new();
+        write_layers(&mut bytes, &dump).unwrap();
+        assert!(bytes.len() < DUMP_MAGIC.len() + 16 + 2 + 2 * escapes.len() * 4);
+
+        let dump = read_layers(&mut &bytes[..]).unwrap();
+        assert_eq!(dump.bounds, (5, 3));
+        assert_eq!(dump.samples, 2);
+        assert_eq!(dump.layer(Coloring::Integer), Some(&counts[..]));
+        assert_eq!(dump.layer(Coloring::Smooth), Some(&escapes[..]));
+    }
+
+    #[test]
+    fn corrupt_dump_headers_are_rejected() {
+        let header = |width: u32, height: u32, samples: u32| {
+            let mut bytes = DUMP_MAGIC.to_vec();
+            for field in [width, height, samples, 1].iter() {
+                bytes.extend_from_slice(&field.to_le_bytes());
+            }
+            bytes.extend_from_slice(&[0, 2]);
+            bytes
+        };
+        for &(width, height, samples) in [(0, 0, 1), (4, 3, 0), (u32::MAX, u32::MAX, 1)].iter() {
+            let error = read_layers(&mut &header(width, height, samples)[..])
+                .err()
+                .unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+        let error = read_layers(&mut &header(u32::MAX, 1, 1)[..]).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn truncated_dump_is_an_error() {
+        let mut dump = Dump::new((4, 4), 1);
+        dump.layers.push((Coloring::Smooth, vec![Some(1.5); 16]));
+        let mut bytes = Vec::new();
+        write_layers(&mut bytes, &dump).unwrap();
+        assert!(read_layers(&mut &bytes[..bytes.len() - 1]).is_err());
+        assert!(read_layers(&mut &b"MBDUMP0\n"[..]).is_err());
+    }
+
+    #[test]
+    fn formats_follow_extensions() {
+        assert_eq!(Format::from_extension("out.PGM"), Some(Format::Pgm));
+        assert_eq!(Format::from_extension("out.dump"), Some(Format::Dump));
+        assert_eq!(Format::from_extension("dir.png/out"), None);
+        assert_eq!(Format::from_name("png16"), Some(Format::Png16));
+    }
+
+    #[test]
+    fn raw_counts_need_integer_coloring() {
+        let options = Options {
+            coloring: Coloring::Smooth,
+            ..Options::default()
+        };
+        let palette = Palette::builtin("gray").unwrap();
+        let error = write_escapes(
+            "unused.raw",
+            &[Some(1.5)],
+            (1, 1),
+            &palette,
+            Format::Raw,
+            &options,
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn pixel_counts_average_escaped_samples() {
+        let escapes = [Some(3.0), Some(6.5), None, None, None, Some(9.0)];
+        assert_eq!(pixel_counts(&escapes, (3, 1)), vec![4, u32::MAX, 9]);
+    }
+}
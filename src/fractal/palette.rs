@@ -0,0 +1,374 @@
+use image::ColorType;
+use std::fs;
+use std::io;
+
+pub type Color = [u8; 4];
+pub type WideColor = [u16; 4];
+
+const BLACK: Color = [0, 0, 0, 255];
+const WHITE: Color = [255, 255, 255, 255];
+
+pub const BUILTIN_NAMES: [&str; 5] = ["gray", "fire", "ocean", "rainbow", "ultra"];
+
+pub struct Palette {
+    stops: Vec<Color>,
+    cycle: Option<u32>,
+    interior: Color,
+}
+
+impl Palette {
+    pub fn builtin(name: &str) -> Option<Palette> {
+        let (stops, cycle) = match name {
+            "gray" => (vec![WHITE, BLACK], None),
+            "fire" => (
+                vec![
+                    [255, 255, 224, 255],
+                    [255, 200, 40, 255],
+                    [220, 60, 0, 255],
+                    [100, 0, 0, 255],
+                    BLACK,
+                ],
+                None,
+            ),
+            "ocean" => (
+                vec![
+                    [224, 255, 255, 255],
+                    [64, 180, 220, 255],
+                    [0, 70, 160, 255],
+                    [0, 10, 50, 255],
+                ],
+                None,
+            ),
+            "rainbow" => (
+                vec![
+                    [255, 0, 0, 255],
+                    [255, 255, 0, 255],
+                    [0, 255, 0, 255],
+                    [0, 255, 255, 255],
+                    [0, 0, 255, 255],
+                    [255, 0, 255, 255],
+                ],
+                Some(48),
+            ),
+            "ultra" => (
+                vec![
+                    [0, 7, 100, 255],
+                    [32, 107, 203, 255],
+                    [237, 255, 255, 255],
+                    [255, 170, 0, 255],
+                    [0, 2, 0, 255],
+                ],
+                Some(64),
+            ),
+            _ => return None,
+        };
+
+        Some(Palette {
+            stops,
+            cycle,
+            interior: BLACK,
+        })
+    }
+
+    pub fn load(filename: &str) -> Result<Palette, io::Error> {
+        let text = fs::read_to_string(filename)?;
+        let invalid = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: invalid palette line `{}`", filename, line),
+            )
+        };
+
+        let mut stops = Vec::new();
+        let mut cycle = None;
+        let mut interior = BLACK;
+        for line in text.lines() {
+            let line = match line.find(';') {
+                Some(index) => &line[..index],
+                None => line,
+            }
+            .trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {}
+                ["cycle", period] => match period.parse() {
+                    Ok(period) if period > 0 => cycle = Some(period),
+                    _ => return Err(invalid(line)),
+                },
+                ["interior", color] => {
+                    interior = parse_color(color).ok_or_else(|| invalid(line))?
+                }
+                [color] => stops.push(parse_color(color).ok_or_else(|| invalid(line))?),
+                _ => return Err(invalid(line)),
+            }
+        }
+
+        if stops.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: palette has no colors", filename),
+            ));
+        }
+
+        Ok(Palette {
+            stops,
+            cycle,
+            interior,
+        })
+    }
+
+    pub fn from_spec(spec: &str) -> Result<Palette, io::Error> {
+        match Palette::builtin(spec) {
+            Some(palette) => Ok(palette),
+            None => Palette::load(spec).map_err(|error| {
+                io::Error::new(
+                    error.kind(),
+                    format!(
+                        "`{}` is not a built-in palette ({}) or a readable palette file: {}",
+                        spec,
+                        BUILTIN_NAMES.join(", "),
+                        error
+                    ),
+                )
+            }),
+        }
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        self.stops
+            .iter()
+            .chain(std::iter::once(&self.interior))
+            .any(|color| color[3] != 255)
+    }
+
+    pub fn channels(&self) -> usize {
+        if self.has_alpha() {
+            4
+        } else {
+            3
+        }
+    }
+
+    pub fn color_type(&self) -> ColorType {
+        if self.has_alpha() {
+            ColorType::RGBA(8)
+        } else {
+            ColorType::RGB(8)
+        }
+    }
+
+    pub fn cycle(&self) -> Option<u32> {
+        self.cycle
+    }
+
+    pub fn wide_color_type(&self) -> ColorType {
+        if self.has_alpha() {
+            ColorType::RGBA(16)
+        } else {
+            ColorType::RGB(16)
+        }
+    }
+
+    pub fn color(&self, escape: Option<f64>, range: (f64, f64)) -> Color {
+        quantize(self.shade(escape, range))
+    }
+
+    pub fn wide_color(&self, escape: Option<f64>, range: (f64, f64)) -> WideColor {
+        quantize_wide(self.shade(escape, range))
+    }
+
+    pub fn shade(&self, escape: Option<f64>, range: (f64, f64)) -> [f64; 4] {
+        let count = match escape {
+            None => return widen(self.interior),
+            Some(count) => count,
+        };
+
+        let n = self.stops.len();
+        if n == 1 {
+            return widen(self.stops[0]);
+        }
+
+        match self.cycle {
+            Some(period) => {
+                let period = period as f64;
+                let position = count % period / period * n as f64;
+                let index = position as usize % n;
+                lerp(
+                    self.stops[index],
+                    self.stops[(index + 1) % n],
+                    position.fract(),
+                )
+            }
+            None => {
+                let (min, max) = range;
+                let normalized = if max > min {
+                    ((count - min) / (max - min)).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let position = normalized * (n - 1) as f64;
+                let index = (position as usize).min(n - 2);
+                lerp(
+                    self.stops[index],
+                    self.stops[index + 1],
+                    position - index as f64,
+                )
+            }
+        }
+    }
+}
+
+fn to_linear(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn from_linear(value: f64) -> f64 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn average<I: Iterator<Item = [f64; 4]>>(shades: I) -> [f64; 4] {
+    linear_mean(shades, 255.0)
+}
+
+pub fn quantize(shade: [f64; 4]) -> Color {
+    let mut color = BLACK;
+    for channel in 0..4 {
+        color[channel] = shade[channel].round().clamp(0.0, 255.0) as u8;
+    }
+    color
+}
+
+pub fn quantize_wide(shade: [f64; 4]) -> WideColor {
+    let mut color = [0, 0, 0, 65535];
+    for channel in 0..4 {
+        color[channel] = (shade[channel] * 257.0).round().clamp(0.0, 65535.0) as u16;
+    }
+    color
+}
+
+fn linear_mean<I: Iterator<Item = [f64; 4]>>(colors: I, scale: f64) -> [f64; 4] {
+    let mut sum = [0.0; 4];
+    let mut count = 0;
+    for color in colors {
+        for channel in 0..3 {
+            sum[channel] += to_linear(color[channel] / scale);
+        }
+        sum[3] += color[3];
+        count += 1;
+    }
+    if count == 0 {
+        return [0.0, 0.0, 0.0, scale];
+    }
+
+    let count = count as f64;
+    [
+        from_linear(sum[0] / count) * scale,
+        from_linear(sum[1] / count) * scale,
+        from_linear(sum[2] / count) * scale,
+        sum[3] / count,
+    ]
+}
+
+fn widen(color: Color) -> [f64; 4] {
+    [
+        color[0] as f64,
+        color[1] as f64,
+        color[2] as f64,
+        color[3] as f64,
+    ]
+}
+
+fn lerp(from: Color, to: Color, t: f64) -> [f64; 4] {
+    let mut color = [0.0; 4];
+    for channel in 0..4 {
+        let (a, b) = (from[channel] as f64, to[channel] as f64);
+        color[channel] = a + (b - a) * t;
+    }
+    color
+}
+
+fn parse_color(s: &str) -> Option<Color> {
+    let hex = s.trim_start_matches('#');
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut color = [255; 4];
+    for (index, channel) in color.iter_mut().enumerate().take(hex.len() / 2) {
+        *channel = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).ok()?;
+    }
+    Some(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn palette_file(name: &str, text: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}-{}.pal", name, std::process::id()));
+        fs::write(&path, text).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn gradients_stretch_over_the_escape_range() {
+        let gray = Palette::builtin("gray").unwrap();
+        assert_eq!(gray.color(Some(10.0), (10.0, 110.0)), WHITE);
+        assert_eq!(gray.color(Some(60.0), (10.0, 110.0)), [128, 128, 128, 255]);
+        assert_eq!(gray.color(Some(110.0), (10.0, 110.0)), BLACK);
+        assert_eq!(gray.color(Some(500.0), (10.0, 110.0)), BLACK);
+        assert_eq!(gray.color(None, (10.0, 110.0)), BLACK);
+        assert_eq!(gray.color_type(), ColorType::RGB(8));
+    }
+
+    #[test]
+    fn cyclic_palettes_repeat_regardless_of_range() {
+        let rainbow = Palette::builtin("rainbow").unwrap();
+        assert_eq!(rainbow.cycle(), Some(48));
+        assert_eq!(rainbow.color(Some(0.0), (0.0, 10.0)), [255, 0, 0, 255]);
+        assert_eq!(rainbow.color(Some(8.0), (0.0, 1000.0)), [255, 255, 0, 255]);
+        assert_eq!(
+            rainbow.color(Some(5.0), (0.0, 10.0)),
+            rainbow.color(Some(5.0 + 48.0 * 3.0), (0.0, 1000.0))
+        );
+    }
+
+    #[test]
+    fn palette_files_read_colors_cycles_and_interiors() {
+        let filename = palette_file(
+            "stripes",
+            "; black and white stripes\n#000000\nffffff80 ; translucent\n\ncycle 4\ninterior ff0000\n",
+        );
+        let stripes = Palette::from_spec(&filename).unwrap();
+        assert_eq!(stripes.cycle(), Some(4));
+        assert_eq!(stripes.channels(), 4);
+        assert_eq!(stripes.color(Some(0.0), (0.0, 1.0)), [0, 0, 0, 255]);
+        assert_eq!(stripes.color(Some(2.0), (0.0, 1.0)), [255, 255, 255, 128]);
+        assert_eq!(stripes.color(None, (0.0, 1.0)), [255, 0, 0, 255]);
+        fs::remove_file(filename).unwrap();
+
+        for text in &[
+            "#12345\n",
+            "cycle 0\nffffff\n",
+            "000000 ffffff\n",
+            "; empty\n",
+        ] {
+            let filename = palette_file("broken", text);
+            assert_eq!(
+                Palette::load(&filename).err().unwrap().kind(),
+                io::ErrorKind::InvalidData,
+                "{:?}",
+                text
+            );
+            fs::remove_file(filename).unwrap();
+        }
+    }
+}
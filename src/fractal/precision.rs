@@ -0,0 +1,466 @@
+use num::{Complex, Num, One, Zero};
+use std::fmt::Debug;
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+use std::str::FromStr;
+
+pub trait Real:
+    Num + Copy + PartialOrd + Neg<Output = Self> + FromStr + Debug + Send + Sync
+{
+    const DIGITS: u32;
+
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+    fn abs(self) -> Self;
+}
+
+impl Real for f32 {
+    const DIGITS: u32 = f32::MANTISSA_DIGITS;
+
+    fn from_f64(value: f64) -> f32 {
+        value as f32
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn abs(self) -> f32 {
+        f32::abs(self)
+    }
+}
+
+impl Real for f64 {
+    const DIGITS: u32 = f64::MANTISSA_DIGITS;
+
+    fn from_f64(value: f64) -> f64 {
+        value
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn abs(self) -> f64 {
+        f64::abs(self)
+    }
+}
+
+pub fn complex_to_f64<T: Real>(point: Complex<T>) -> Complex<f64> {
+    Complex {
+        re: point.re.to_f64(),
+        im: point.im.to_f64(),
+    }
+}
+
+pub fn complex_from_f64<T: Real>(point: Complex<f64>) -> Complex<T> {
+    Complex {
+        re: T::from_f64(point.re),
+        im: T::from_f64(point.im),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Precision {
+    Single,
+    Double,
+    DoubleDouble,
+    Deep,
+}
+
+impl Precision {
+    pub fn from_name(name: &str) -> Option<Precision> {
+        match name {
+            "f32" => Some(Precision::Single),
+            "f64" => Some(Precision::Double),
+            "dd" => Some(Precision::DoubleDouble),
+            "deep" => Some(Precision::Deep),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Precision::Single => "f32",
+            Precision::Double => "f64",
+            Precision::DoubleDouble => "dd",
+            Precision::Deep => "deep",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Default)]
+pub struct DoubleDouble {
+    pub hi: f64,
+    pub lo: f64,
+}
+
+fn two_sum(a: f64, b: f64) -> DoubleDouble {
+    let hi = a + b;
+    let b_virtual = hi - a;
+    let lo = (a - (hi - b_virtual)) + (b - b_virtual);
+    DoubleDouble { hi, lo }
+}
+
+fn quick_two_sum(a: f64, b: f64) -> DoubleDouble {
+    let hi = a + b;
+    DoubleDouble {
+        hi,
+        lo: b - (hi - a),
+    }
+}
+
+fn two_product(a: f64, b: f64) -> DoubleDouble {
+    let hi = a * b;
+    DoubleDouble {
+        hi,
+        lo: a.mul_add(b, -hi),
+    }
+}
+
+impl DoubleDouble {
+    pub fn new(value: f64) -> DoubleDouble {
+        DoubleDouble { hi: value, lo: 0.0 }
+    }
+
+    fn scale(self, factor: f64) -> DoubleDouble {
+        let product = two_product(self.hi, factor);
+        quick_two_sum(product.hi, product.lo + self.lo * factor)
+    }
+
+    fn trunc(self) -> DoubleDouble {
+        let hi = self.hi.trunc();
+        if hi != self.hi {
+            return DoubleDouble::new(hi);
+        }
+        quick_two_sum(hi, self.lo.trunc())
+    }
+}
+
+impl Add for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn add(self, other: DoubleDouble) -> DoubleDouble {
+        let sum = two_sum(self.hi, other.hi);
+        let low = two_sum(self.lo, other.lo);
+        let sum = quick_two_sum(sum.hi, sum.lo + low.hi);
+        quick_two_sum(sum.hi, sum.lo + low.lo)
+    }
+}
+
+impl Neg for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn neg(self) -> DoubleDouble {
+        DoubleDouble {
+            hi: -self.hi,
+            lo: -self.lo,
+        }
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn sub(self, other: DoubleDouble) -> DoubleDouble {
+        self + -other
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn mul(self, other: DoubleDouble) -> DoubleDouble {
+        let product = two_product(self.hi, other.hi);
+        let cross = self.hi * other.lo + self.lo * other.hi;
+        quick_two_sum(product.hi, product.lo + cross)
+    }
+}
+
+impl Div for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn div(self, other: DoubleDouble) -> DoubleDouble {
+        let first = self.hi / other.hi;
+        let remainder = self - other.scale(first);
+        let second = remainder.hi / other.hi;
+        let remainder = remainder - other.scale(second);
+        let third = remainder.hi / other.hi;
+        quick_two_sum(first, second) + DoubleDouble::new(third)
+    }
+}
+
+impl Rem for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn rem(self, other: DoubleDouble) -> DoubleDouble {
+        self - other * (self / other).trunc()
+    }
+}
+
+impl Zero for DoubleDouble {
+    fn zero() -> DoubleDouble {
+        DoubleDouble::new(0.0)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.hi == 0.0
+    }
+}
+
+impl One for DoubleDouble {
+    fn one() -> DoubleDouble {
+        DoubleDouble::new(1.0)
+    }
+}
+
+impl Num for DoubleDouble {
+    type FromStrRadixErr = ParseDoubleDoubleError;
+
+    fn from_str_radix(text: &str, radix: u32) -> Result<DoubleDouble, ParseDoubleDoubleError> {
+        if radix != 10 {
+            return Err(ParseDoubleDoubleError);
+        }
+        text.parse()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ParseDoubleDoubleError;
+
+const MAX_EXPONENT: i32 = 308;
+
+impl FromStr for DoubleDouble {
+    type Err = ParseDoubleDoubleError;
+
+    fn from_str(text: &str) -> Result<DoubleDouble, ParseDoubleDoubleError> {
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (mantissa, exponent) = match text.find(['e', 'E']) {
+            Some(index) => (
+                &text[..index],
+                text[index + 1..]
+                    .parse::<i32>()
+                    .map_err(|_| ParseDoubleDoubleError)?,
+            ),
+            None => (text, 0),
+        };
+        if !(-MAX_EXPONENT..=MAX_EXPONENT).contains(&exponent) {
+            return Err(ParseDoubleDoubleError);
+        }
+
+        let mut value = DoubleDouble::zero();
+        let mut exponent = exponent;
+        let mut seen_point = false;
+        let mut digits = 0;
+        for c in mantissa.chars() {
+            match c {
+                '.' if !seen_point => seen_point = true,
+                '0'..='9' => {
+                    let digit = c as u32 - '0' as u32;
+                    value = value.scale(10.0) + DoubleDouble::new(digit as f64);
+                    digits += 1;
+                    if seen_point {
+                        exponent -= 1;
+                    }
+                }
+                _ => return Err(ParseDoubleDoubleError),
+            }
+        }
+        if digits == 0 {
+            return Err(ParseDoubleDoubleError);
+        }
+
+        if !(-MAX_EXPONENT..=MAX_EXPONENT).contains(&exponent) {
+            return Err(ParseDoubleDoubleError);
+        }
+
+        let mut power = DoubleDouble::one();
+        let mut square = DoubleDouble::new(10.0);
+        let mut remaining = exponent.unsigned_abs();
+        while remaining > 0 {
+            if remaining & 1 != 0 {
+                power = power * square;
+            }
+            square = square * square;
+            remaining >>= 1;
+        }
+        value = if exponent < 0 {
+            value / power
+        } else {
+            value * power
+        };
+        if !value.hi.is_finite() || !value.lo.is_finite() {
+            return Err(ParseDoubleDoubleError);
+        }
+        Ok(if negative { -value } else { value })
+    }
+}
+
+impl Real for DoubleDouble {
+    const DIGITS: u32 = 2 * f64::MANTISSA_DIGITS;
+
+    fn from_f64(value: f64) -> DoubleDouble {
+        DoubleDouble::new(value)
+    }
+
+    fn to_f64(self) -> f64 {
+        self.hi + self.lo
+    }
+
+    fn abs(self) -> DoubleDouble {
+        if self.hi < 0.0 {
+            -self
+        } else {
+            self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fractal::{
+        deep, frame_corners, parse_center, parse_complex, render_deep, render_precise, Coloring,
+        Fractal, Options, Region, Rotation, View,
+    };
+
+    const SIZE: (usize, usize) = (48, 36);
+
+    fn escapes<T: Real>(
+        center: &str,
+        width: f64,
+        degrees: f64,
+        options: &Options,
+    ) -> Vec<Option<f32>> {
+        let center: Complex<T> = parse_complex(center).unwrap();
+        let (upper_left, lower_right) = frame_corners(Complex::zero(), width, SIZE);
+        let view = View {
+            bounds: SIZE,
+            upper_left,
+            lower_right,
+            rotation: Rotation::new(Complex::zero(), degrees),
+        };
+        let mut escapes = vec![None; SIZE.0 * SIZE.1];
+        render_precise(
+            &mut escapes,
+            view,
+            Region::full(SIZE),
+            center,
+            Fractal::Mandelbrot,
+            options,
+        );
+        escapes
+    }
+
+    fn agreement(a: &[Option<f32>], b: &[Option<f32>], tolerance: f32) -> f64 {
+        let close = |(a, b): &(&Option<f32>, &Option<f32>)| match (a, b) {
+            (Some(a), Some(b)) => (a - b).abs() <= tolerance,
+            (a, b) => a == b,
+        };
+        let equal = a.iter().zip(b).filter(close).count();
+        equal as f64 / a.len() as f64
+    }
+
+    fn distinct(escapes: &[Option<f32>]) -> usize {
+        let mut values: Vec<Option<u32>> = escapes
+            .iter()
+            .map(|escape| escape.map(f32::to_bits))
+            .collect();
+        values.sort();
+        values.dedup();
+        values.len()
+    }
+
+    fn close(a: DoubleDouble, b: DoubleDouble, tolerance: f64) -> bool {
+        (a - b).abs().to_f64() <= tolerance * b.abs().to_f64()
+    }
+
+    #[test]
+    fn double_double_arithmetic_keeps_about_thirty_digits() {
+        let one = DoubleDouble::one();
+        let three = DoubleDouble::new(3.0);
+        let third = one / three;
+        assert!(third.lo != 0.0);
+        assert!(close(third * three, one, 1e-31));
+        assert!(close(third + third + third, one, 1e-31));
+
+        let tiny = DoubleDouble::new(1e-20);
+        assert_eq!((one + tiny - one).to_f64(), 1e-20);
+        assert_eq!(
+            (DoubleDouble::new(7.5) % DoubleDouble::new(2.0)).to_f64(),
+            1.5
+        );
+    }
+
+    #[test]
+    fn parsing_keeps_digits_beyond_f64() {
+        let parsed: DoubleDouble = "0.1".parse().unwrap();
+        assert!(close(
+            parsed * DoubleDouble::new(10.0),
+            DoubleDouble::one(),
+            1e-31
+        ));
+        assert!(parsed != DoubleDouble::new(0.1));
+
+        let long: DoubleDouble = "-0.74364388703715870475".parse().unwrap();
+        let short: DoubleDouble = "-0.7436438870371587".parse().unwrap();
+        assert!((long - short).abs().to_f64() > 1e-18);
+        assert_eq!(long.to_f64(), -0.7436438870371587);
+
+        assert_eq!("1.5e3".parse::<DoubleDouble>().unwrap().to_f64(), 1500.0);
+        assert!("1.2.3".parse::<DoubleDouble>().is_err());
+        assert!("".parse::<DoubleDouble>().is_err());
+        assert!("1e99999999".parse::<DoubleDouble>().is_err());
+        assert!("1e-2147483648".parse::<DoubleDouble>().is_err());
+        assert!("9e308".parse::<DoubleDouble>().is_err());
+        assert_eq!("1e-300".parse::<DoubleDouble>().unwrap().to_f64(), 1e-300);
+    }
+
+    #[test]
+    fn precisions_agree_on_a_shallow_view() {
+        let options = Options {
+            limit: 500,
+            ..Options::default()
+        };
+        let center = "-0.7436,0.1318";
+        let single = escapes::<f32>(center, 0.01, 0.0, &options);
+        let double = escapes::<f64>(center, 0.01, 0.0, &options);
+        let double_double = escapes::<DoubleDouble>(center, 0.01, 0.0, &options);
+        assert!(distinct(&double) > 50);
+        assert!(agreement(&double_double, &double, 0.0) > 0.99);
+        assert!(agreement(&single, &double, 2.0) > 0.85);
+    }
+
+    #[test]
+    fn double_double_resolves_zooms_beyond_f64() {
+        let options = Options {
+            limit: 1000,
+            coloring: Coloring::Smooth,
+            ..Options::default()
+        };
+        let center = "-1.99999999999999999,0";
+        let width = 1e-18;
+        let double = escapes::<f64>(center, width, 0.0, &options);
+        assert!(distinct(&double) < 5);
+
+        let bits = deep::precision_for(width / SIZE.0 as f64);
+        let orbit = deep::reference_orbit(&parse_center(center, bits).unwrap(), options.limit);
+        let origin = Complex::zero();
+        let (upper_left, lower_right) = frame_corners(origin, width, SIZE);
+        for &degrees in [0.0, 30.0].iter() {
+            let double_double = escapes::<DoubleDouble>(center, width, degrees, &options);
+            let view = View {
+                bounds: SIZE,
+                upper_left,
+                lower_right,
+                rotation: Rotation::new(origin, degrees),
+            };
+            let mut perturbed = vec![None; SIZE.0 * SIZE.1];
+            render_deep(&mut perturbed, view, Region::full(SIZE), &orbit, &options);
+            assert!(distinct(&double_double) > 50);
+            assert!(agreement(&double_double, &perturbed, 1e-3) > 0.99);
+        }
+    }
+}
@@ -0,0 +1,462 @@
+use super::palette::{self, Palette};
+use super::{
+    complex_from_f64, deep, distance_estimate, escape_orbit, escape_value, fill, orbit_value, simd,
+    Coloring, Fractal, Real, Region, Sampling, TileScheduler, View,
+};
+use num::Complex;
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub palette: String,
+    pub coloring: Coloring,
+    pub limit: u32,
+    pub sampling: Sampling,
+    pub vectorize: bool,
+    pub fill: bool,
+    pub equalize: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            palette: "gray".to_string(),
+            coloring: Coloring::Integer,
+            limit: 255,
+            sampling: Sampling::Single,
+            vectorize: true,
+            fill: false,
+            equalize: false,
+        }
+    }
+}
+
+impl Options {
+    pub fn fixed_range(&self) -> (f64, f64) {
+        match self.coloring {
+            _ if self.equalize => (0.0, 1.0),
+            Coloring::Distance => (0.0, 1.0),
+            _ => (0.0, self.limit as f64),
+        }
+    }
+}
+
+pub fn render(
+    escapes: &mut [Option<f32>],
+    view: View,
+    region: Region,
+    fractal: Fractal,
+    options: &Options,
+) {
+    let pixel_size = view.pixel_size();
+    let escape = |points: &[Complex<f64>], escapes: &mut [Option<f32>]| {
+        kernel_values(fractal, points, options, pixel_size, escapes)
+    };
+    if options.fill {
+        fill::render_filled(escapes, view, region, escape);
+    } else {
+        render_samples(escapes, view, region, options.sampling, escape);
+    }
+}
+
+fn kernel_values(
+    fractal: Fractal,
+    points: &[Complex<f64>],
+    options: &Options,
+    pixel_size: f64,
+    escapes: &mut [Option<f32>],
+) {
+    if options.coloring == Coloring::Distance {
+        for (point, escape) in points.iter().zip(escapes) {
+            *escape = distance_estimate(fractal, *point, options.limit)
+                .map(|distance| (1.0 / (1.0 + distance / pixel_size)) as f32);
+        }
+    } else if options.vectorize {
+        simd::escape_values(fractal, points, options.limit, options.coloring, escapes);
+    } else {
+        for (point, escape) in points.iter().zip(escapes) {
+            *escape =
+                escape_value(fractal, *point, options.limit, options.coloring).map(|v| v as f32);
+        }
+    }
+}
+
+pub fn render_samples<T, F>(
+    escapes: &mut [Option<f32>],
+    view: View<T>,
+    region: Region,
+    sampling: Sampling,
+    escape: F,
+) where
+    T: Real,
+    F: Fn(&[Complex<T>], &mut [Option<f32>]),
+{
+    let samples = sampling.samples();
+    assert!(escapes.len() == region.pixels() * samples);
+
+    let row_length = region.width * samples;
+    let mut points = Vec::with_capacity(row_length);
+    for row in region.top..region.top + region.height {
+        points.clear();
+        row_points(&mut points, view, region, row, sampling);
+
+        let offset = (row - region.top) * row_length;
+        let row_escapes = &mut escapes[offset..offset + row_length];
+        escape(&points, &mut row_escapes[..points.len()]);
+        if points.len() < row_length {
+            for column in (0..region.width).rev() {
+                let value = row_escapes[column];
+                for slot in &mut row_escapes[column * samples..(column + 1) * samples] {
+                    *slot = value;
+                }
+            }
+        }
+    }
+}
+
+fn row_points<T: Real>(
+    points: &mut Vec<Complex<T>>,
+    view: View<T>,
+    region: Region,
+    row: usize,
+    sampling: Sampling,
+) {
+    let samples = sampling.samples();
+    for column in region.left..region.left + region.width {
+        let point = view.pixel_to_point((column, row));
+        match sampling {
+            Sampling::Single | Sampling::Adaptive(_) => points.push(point),
+            Sampling::Grid(_) | Sampling::Jitter(_) => {
+                let seed =
+                    point.re.to_f64().to_bits() ^ point.im.to_f64().to_bits().rotate_left(32);
+                for index in 0..samples {
+                    let (dx, dy) = sampling.offset(index, seed);
+                    let sample = (column as f64 + dx, row as f64 + dy);
+                    points.push(view.sample_to_point(sample));
+                }
+            }
+        }
+    }
+}
+
+pub fn render_layers(
+    escapes: &mut [Option<f32>],
+    view: View,
+    region: Region,
+    fractal: Fractal,
+    options: &Options,
+    other: Coloring,
+) {
+    let samples = options.sampling.samples();
+    assert!(escapes.len() == region.pixels() * samples * 2);
+    assert!(!options.fill);
+
+    let pixel_size = view.pixel_size();
+    let value = |point: Complex<f64>, orbit: Option<(u32, f64)>, coloring: Coloring| {
+        match coloring {
+            Coloring::Distance => distance_estimate(fractal, point, options.limit)
+                .map(|distance| 1.0 / (1.0 + distance / pixel_size)),
+            Coloring::Integer | Coloring::Smooth => orbit_value(fractal, orbit, coloring),
+        }
+        .map(|value| value as f32)
+    };
+
+    let row_length = region.width * samples;
+    let mut points = Vec::with_capacity(row_length);
+    let mut orbits = vec![None; row_length];
+    for row in region.top..region.top + region.height {
+        points.clear();
+        row_points(&mut points, view, region, row, options.sampling);
+        assert!(points.len() == row_length);
+        if options.vectorize {
+            simd::escape_orbits(fractal, &points, options.limit, &mut orbits);
+        } else {
+            for (point, orbit) in points.iter().zip(&mut orbits) {
+                *orbit = escape_orbit(fractal, *point, options.limit);
+            }
+        }
+
+        let offset = (row - region.top) * row_length * 2;
+        for (index, (point, orbit)) in points.iter().zip(&orbits).enumerate() {
+            let slot = offset + index / samples * samples * 2 + index % samples;
+            escapes[slot] = value(*point, *orbit, options.coloring);
+            escapes[slot + samples] = value(*point, *orbit, other);
+        }
+    }
+}
+
+pub fn render_precise<T: Real>(
+    escapes: &mut [Option<f32>],
+    view: View,
+    region: Region,
+    center: Complex<T>,
+    fractal: Fractal,
+    options: &Options,
+) {
+    let pixel_size = view.pixel_size();
+    let escape = |offsets: &[Complex<f64>], escapes: &mut [Option<f32>]| {
+        for (offset, escape) in offsets.iter().zip(escapes) {
+            let point = center + complex_from_f64(*offset);
+            let value = escape_value(fractal, point, options.limit, options.coloring);
+            *escape = match options.coloring {
+                Coloring::Distance => value.map(|distance| 1.0 / (1.0 + distance / pixel_size)),
+                Coloring::Integer | Coloring::Smooth => value,
+            }
+            .map(|value| value as f32);
+        }
+    };
+    render_samples(escapes, view, region, options.sampling, escape);
+}
+
+pub fn refine_adaptive<F>(
+    escapes: &mut [Option<f32>],
+    view: View,
+    region: Region,
+    sampling: Sampling,
+    coloring: Coloring,
+    scheduler: &TileScheduler,
+    escape: F,
+) where
+    F: Fn(&[Complex<f64>], &mut [Option<f32>]) + Sync,
+{
+    let samples = sampling.samples();
+    let centers: Vec<Option<f32>> = escapes.chunks(samples).map(|pixel| pixel[0]).collect();
+    let threshold = match coloring {
+        Coloring::Distance => 1.0 / 64.0,
+        Coloring::Integer | Coloring::Smooth => 1.0,
+    };
+    let differs = |a: Option<f32>, b: Option<f32>| match (a, b) {
+        (Some(a), Some(b)) => (a - b).abs() >= threshold,
+        (None, None) => false,
+        _ => true,
+    };
+
+    let width = region.width;
+    scheduler.render(escapes, region, |tile, tile_region| {
+        for tile_row in 0..tile_region.height {
+            for tile_column in 0..tile_region.width {
+                let (x, y) = (tile_region.left + tile_column, tile_region.top + tile_row);
+                let (row, column) = (y - region.top, x - region.left);
+                let index = tile_row * tile_region.width + tile_column;
+                let pixel = &mut tile[index * samples..(index + 1) * samples];
+
+                let center = centers[row * width + column];
+                let mut neighbors = Vec::with_capacity(4);
+                if column > 0 {
+                    neighbors.push(centers[row * width + column - 1]);
+                }
+                if column + 1 < width {
+                    neighbors.push(centers[row * width + column + 1]);
+                }
+                if row > 0 {
+                    neighbors.push(centers[(row - 1) * width + column]);
+                }
+                if row + 1 < region.height {
+                    neighbors.push(centers[(row + 1) * width + column]);
+                }
+                if !neighbors
+                    .into_iter()
+                    .any(|neighbor| differs(center, neighbor))
+                {
+                    for slot in pixel.iter_mut() {
+                        *slot = center;
+                    }
+                    continue;
+                }
+
+                let points: Vec<Complex<f64>> = (0..samples)
+                    .map(|index| {
+                        let (dx, dy) = sampling.offset(index, 0);
+                        let sample = (x as f64 + dx, y as f64 + dy);
+                        view.sample_to_point(sample)
+                    })
+                    .collect();
+                escape(&points, pixel);
+            }
+        }
+    });
+}
+
+pub fn escape_range(escapes: &[Option<f32>]) -> (f64, f64) {
+    let mut range: Option<(f32, f32)> = None;
+    for &value in escapes.iter().flatten() {
+        range = Some(match range {
+            None => (value, value),
+            Some((min, max)) => (min.min(value), max.max(value)),
+        });
+    }
+
+    match range {
+        Some((min, max)) => (min as f64, max as f64),
+        None => (0.0, 0.0),
+    }
+}
+
+pub fn colorize(pixels: &mut [u8], escapes: &[Option<f32>], palette: &Palette, range: (f64, f64)) {
+    if pixels.is_empty() {
+        return;
+    }
+    let channels = palette.channels();
+    let samples = escapes.len() * channels / pixels.len();
+    assert!(pixels.len() * samples == escapes.len() * channels);
+
+    for (pixel, escapes) in pixels.chunks_mut(channels).zip(escapes.chunks(samples)) {
+        let color = if samples == 1 {
+            palette.color(escapes[0].map(|value| value as f64), range)
+        } else {
+            palette::quantize(palette::average(
+                escapes
+                    .iter()
+                    .map(|escape| palette.shade(escape.map(|value| value as f64), range)),
+            ))
+        };
+        pixel.copy_from_slice(&color[..channels]);
+    }
+}
+
+pub fn colorize_wide(
+    pixels: &mut [u16],
+    escapes: &[Option<f32>],
+    palette: &Palette,
+    range: (f64, f64),
+) {
+    if pixels.is_empty() {
+        return;
+    }
+    let channels = palette.channels();
+    let samples = escapes.len() * channels / pixels.len();
+    assert!(pixels.len() * samples == escapes.len() * channels);
+
+    for (pixel, escapes) in pixels.chunks_mut(channels).zip(escapes.chunks(samples)) {
+        let color = if samples == 1 {
+            palette.wide_color(escapes[0].map(|value| value as f64), range)
+        } else {
+            palette::quantize_wide(palette::average(
+                escapes
+                    .iter()
+                    .map(|escape| palette.shade(escape.map(|value| value as f64), range)),
+            ))
+        };
+        pixel.copy_from_slice(&color[..channels]);
+    }
+}
+
+pub fn render_deep(
+    escapes: &mut [Option<f32>],
+    view: View,
+    region: Region,
+    orbit: &[Complex<f64>],
+    options: &Options,
+) {
+    let escape = |deltas: &[Complex<f64>], escapes: &mut [Option<f32>]| {
+        deep_values(orbit, deltas, options, escapes)
+    };
+    if options.fill {
+        fill::render_filled(escapes, view, region, escape);
+    } else {
+        render_samples(escapes, view, region, options.sampling, escape);
+    }
+}
+
+pub fn deep_values(
+    orbit: &[Complex<f64>],
+    deltas: &[Complex<f64>],
+    options: &Options,
+    escapes: &mut [Option<f32>],
+) {
+    for (delta, escape) in deltas.iter().zip(escapes) {
+        let orbit = deep::perturbed_escape(orbit, *delta, options.limit);
+        *escape = orbit_value(Fractal::Mandelbrot, orbit, options.coloring).map(|v| v as f32);
+    }
+}
+
+pub fn render_view(
+    escapes: &mut [Option<f32>],
+    view: View,
+    region: Region,
+    fractal: Fractal,
+    options: &Options,
+    scheduler: &TileScheduler,
+) {
+    scheduler.render(escapes, region, |tile, tile_region| {
+        render(tile, view, tile_region, fractal, options)
+    });
+
+    if let Sampling::Adaptive(_) = options.sampling {
+        let pixel_size = view.pixel_size();
+        refine_adaptive(
+            escapes,
+            view,
+            region,
+            options.sampling,
+            options.coloring,
+            scheduler,
+            |points, escapes| kernel_values(fractal, points, options, pixel_size, escapes),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fractal::Rotation;
+
+    #[test]
+    fn samples_are_averaged_before_quantizing() {
+        let palette = Palette::builtin("gray").unwrap();
+        let mut pixels = vec![0; palette.channels()];
+        colorize(
+            &mut pixels,
+            &[Some(998.0), Some(1000.0)],
+            &palette,
+            (0.0, 1000.0),
+        );
+        assert!(pixels.iter().all(|&channel| channel == 0));
+
+        let mut wide = vec![0; palette.channels()];
+        colorize_wide(
+            &mut wide,
+            &[Some(998.0), Some(1000.0)],
+            &palette,
+            (0.0, 1000.0),
+        );
+        assert!(wide.iter().all(|&channel| channel == 66));
+    }
+
+    #[test]
+    fn colorizing_an_empty_image_is_a_no_op() {
+        let palette = Palette::builtin("fire").unwrap();
+        colorize(&mut [], &[], &palette, (0.0, 1.0));
+        colorize_wide(&mut [], &[], &palette, (0.0, 1.0));
+    }
+
+    #[test]
+    fn distance_edges_below_one_are_refined() {
+        let refined = |coloring| {
+            let mut escapes: Vec<Option<f32>> = [0.2, 0.6, 0.6]
+                .iter()
+                .flat_map(|&value| vec![Some(value); 4])
+                .collect();
+            let view = View {
+                bounds: (3, 1),
+                upper_left: Complex { re: -1.0, im: 1.0 },
+                lower_right: Complex { re: 1.0, im: -1.0 },
+                rotation: Rotation::none(),
+            };
+            refine_adaptive(
+                &mut escapes,
+                view,
+                Region::full((3, 1)),
+                Sampling::Adaptive(2),
+                coloring,
+                &TileScheduler::new(),
+                |_, escapes| escapes.iter_mut().for_each(|escape| *escape = Some(9.0)),
+            );
+            escapes
+                .chunks(4)
+                .map(|pixel| pixel[0] == Some(9.0))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(refined(Coloring::Distance), vec![true, true, false]);
+        assert_eq!(refined(Coloring::Smooth), vec![false, false, false]);
+    }
+}
@@ -0,0 +1,433 @@
+use super::palette::Palette;
+use super::{
+    colorize, complex_from_f64, deep, deep_values, escape_range, frame_corners, parse_center,
+    parse_complex, refine_adaptive, render_deep, render_layers, render_precise, render_view,
+    shaded_for, write_image, Coloring, DoubleDouble, Fractal, Options, Precision, Real, Region,
+    Rotation, Sampling, TileScheduler, View,
+};
+use image::ColorType;
+use num::{Complex, Zero};
+use std::io;
+
+type Layers = (Vec<Option<f32>>, Vec<Option<f32>>);
+
+#[derive(Clone, Debug)]
+pub struct RenderRequest {
+    pub fractal: Fractal,
+    pub bounds: (usize, usize),
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    pub rotation: Rotation,
+    pub precision: Precision,
+    pub center: Option<Center>,
+    pub options: Options,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Center {
+    pub text: String,
+    pub width: f64,
+}
+
+pub struct ImageBuffer {
+    pub width: usize,
+    pub height: usize,
+    pub color_type: ColorType,
+    pub pixels: Vec<u8>,
+}
+
+impl RenderRequest {
+    pub fn new(
+        bounds: (usize, usize),
+        upper_left: Complex<f64>,
+        lower_right: Complex<f64>,
+    ) -> RenderRequest {
+        RenderRequest {
+            fractal: Fractal::Mandelbrot,
+            bounds,
+            upper_left,
+            lower_right,
+            rotation: Rotation::none(),
+            precision: Precision::Double,
+            center: None,
+            options: Options::default(),
+        }
+    }
+
+    pub fn view(&self) -> View {
+        View {
+            bounds: self.bounds,
+            upper_left: self.upper_left,
+            lower_right: self.lower_right,
+            rotation: self.rotation,
+        }
+    }
+
+    pub fn escapes(&self) -> Result<Vec<Option<f32>>, io::Error> {
+        self.escapes_with(&TileScheduler::new())
+    }
+
+    pub fn escapes_with(&self, scheduler: &TileScheduler) -> Result<Vec<Option<f32>>, io::Error> {
+        match self.precision {
+            Precision::Single => return self.precise_escapes::<f32>(scheduler),
+            Precision::DoubleDouble => return self.precise_escapes::<DoubleDouble>(scheduler),
+            Precision::Deep => return self.deep_escapes(scheduler),
+            Precision::Double => {}
+        }
+
+        let samples = self.options.sampling.samples();
+        let mut escapes = vec![None; self.bounds.0 * self.bounds.1 * samples];
+        render_view(
+            &mut escapes,
+            self.view(),
+            Region::full(self.bounds),
+            self.fractal,
+            &self.options,
+            scheduler,
+        );
+        Ok(escapes)
+    }
+
+    fn precise_escapes<T: Real>(
+        &self,
+        scheduler: &TileScheduler,
+    ) -> Result<Vec<Option<f32>>, io::Error> {
+        if self.options.fill || matches!(self.options.sampling, Sampling::Adaptive(_)) {
+            return Err(invalid_input(format!(
+                "{} renders cannot be filled or adaptively supersampled",
+                self.precision.name()
+            )));
+        }
+
+        let half = (self.lower_right - self.upper_left) / 2.0;
+        let midpoint = complex_from_f64::<T>(self.upper_left) + complex_from_f64(half);
+        let (center, upper_left, lower_right) = match &self.center {
+            Some(center) => {
+                let (upper_left, lower_right) =
+                    frame_corners(Complex::zero(), center.width, self.bounds);
+                let point = parse_complex(&center.text).ok_or_else(|| malformed(center))?;
+                (point, upper_left, lower_right)
+            }
+            None => (midpoint, -half, half),
+        };
+        let view = View {
+            bounds: self.bounds,
+            upper_left,
+            lower_right,
+            rotation: Rotation::new(Complex::zero(), self.rotation.degrees()),
+        };
+
+        let samples = self.options.sampling.samples();
+        let mut escapes = vec![None; self.bounds.0 * self.bounds.1 * samples];
+        scheduler.render(&mut escapes, Region::full(self.bounds), |tile, region| {
+            render_precise(tile, view, region, center, self.fractal, &self.options)
+        });
+        Ok(escapes)
+    }
+
+    fn deep_escapes(&self, scheduler: &TileScheduler) -> Result<Vec<Option<f32>>, io::Error> {
+        let midpoint = (self.upper_left + self.lower_right) / 2.0;
+        let width = match &self.center {
+            Some(center) => center.width,
+            None => self.lower_right.re - self.upper_left.re,
+        };
+        let bits = deep::precision_for(width / self.bounds.0 as f64);
+        let center = match &self.center {
+            Some(center) => parse_center(&center.text, bits).ok_or_else(|| malformed(center))?,
+            None => (
+                deep::Fixed::from_f64(midpoint.re, bits),
+                deep::Fixed::from_f64(midpoint.im, bits),
+            ),
+        };
+        let orbit = deep::reference_orbit(&center, self.options.limit);
+
+        let origin = Complex::zero();
+        let (upper_left, lower_right) = frame_corners(origin, width, self.bounds);
+        let view = View {
+            bounds: self.bounds,
+            upper_left,
+            lower_right,
+            rotation: Rotation::new(origin, self.rotation.degrees()),
+        };
+        let samples = self.options.sampling.samples();
+        let mut escapes = vec![None; self.bounds.0 * self.bounds.1 * samples];
+        scheduler.render(&mut escapes, Region::full(self.bounds), |tile, region| {
+            render_deep(tile, view, region, &orbit, &self.options)
+        });
+        if let Sampling::Adaptive(_) = self.options.sampling {
+            refine_adaptive(
+                &mut escapes,
+                view,
+                Region::full(self.bounds),
+                self.options.sampling,
+                self.options.coloring,
+                scheduler,
+                |deltas, escapes| deep_values(&orbit, deltas, &self.options, escapes),
+            );
+        }
+        Ok(escapes)
+    }
+
+    pub fn layers_with(
+        &self,
+        other: Coloring,
+        scheduler: &TileScheduler,
+    ) -> Result<Layers, io::Error> {
+        let adaptive = matches!(self.options.sampling, Sampling::Adaptive(_));
+        if self.options.fill || adaptive || self.precision != Precision::Double {
+            let mut request = self.clone();
+            request.options.coloring = other;
+            return Ok((
+                self.escapes_with(scheduler)?,
+                request.escapes_with(scheduler)?,
+            ));
+        }
+
+        let samples = self.options.sampling.samples();
+        let mut paired = vec![None; self.bounds.0 * self.bounds.1 * samples * 2];
+        scheduler.render(&mut paired, Region::full(self.bounds), |tile, region| {
+            render_layers(
+                tile,
+                self.view(),
+                region,
+                self.fractal,
+                &self.options,
+                other,
+            )
+        });
+        let mut escapes = Vec::with_capacity(paired.len() / 2);
+        let mut others = Vec::with_capacity(paired.len() / 2);
+        for pixel in paired.chunks(samples * 2) {
+            escapes.extend_from_slice(&pixel[..samples]);
+            others.extend_from_slice(&pixel[samples..]);
+        }
+        Ok((escapes, others))
+    }
+
+    pub fn render(&self) -> Result<ImageBuffer, io::Error> {
+        let palette = Palette::from_spec(&self.options.palette)?;
+        let escapes = self.escapes()?;
+        let shaded = shaded_for(&escapes, &palette, &self.options);
+        Ok(ImageBuffer::from_escapes(&shaded, self.bounds, &palette))
+    }
+}
+
+fn invalid_input(reason: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, reason)
+}
+
+fn malformed(center: &Center) -> io::Error {
+    invalid_input(format!("malformed center `{}`", center.text))
+}
+
+impl ImageBuffer {
+    pub fn from_escapes(
+        escapes: &[Option<f32>],
+        bounds: (usize, usize),
+        palette: &Palette,
+    ) -> ImageBuffer {
+        ImageBuffer::from_escapes_in(escapes, bounds, palette, escape_range(escapes))
+    }
+
+    pub fn from_escapes_in(
+        escapes: &[Option<f32>],
+        bounds: (usize, usize),
+        palette: &Palette,
+        range: (f64, f64),
+    ) -> ImageBuffer {
+        let mut pixels = vec![0; bounds.0 * bounds.1 * palette.channels()];
+        colorize(&mut pixels, escapes, palette, range);
+        ImageBuffer {
+            width: bounds.0,
+            height: bounds.1,
+            color_type: palette.color_type(),
+            pixels,
+        }
+    }
+
+    pub fn save(&self, filename: &str) -> Result<(), io::Error> {
+        write_image(
+            filename,
+            &self.pixels,
+            (self.width, self.height),
+            self.color_type,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_fills_every_pixel() {
+        let mut request = RenderRequest::new(
+            (40, 30),
+            Complex { re: -2.5, im: 1.2 },
+            Complex { re: 1.0, im: -1.2 },
+        );
+        request.options.palette = "fire".to_string();
+        let image = request.render().unwrap();
+        assert_eq!((image.width, image.height), (40, 30));
+        assert_eq!(image.pixels.len(), 40 * 30 * 3);
+        assert!(image.pixels.chunks(3).any(|pixel| pixel != [0, 0, 0]));
+        assert!(image.pixels.chunks(3).any(|pixel| pixel == [0, 0, 0]));
+    }
+
+    #[test]
+    fn high_limits_are_normalized_rather_than_clipped() {
+        let mut request = RenderRequest::new(
+            (40, 30),
+            Complex {
+                re: -0.7440,
+                im: 0.1325,
+            },
+            Complex {
+                re: -0.7432,
+                im: 0.1319,
+            },
+        );
+        request.options.limit = 20_000;
+        let escapes = request.escapes().unwrap();
+        assert!(escape_range(&escapes).1 > 500.0);
+        request.options.limit = 255;
+        let clipped = request.escapes().unwrap();
+        let interior = |escapes: &[Option<f32>]| escapes.iter().filter(|e| e.is_none()).count();
+        assert!(interior(&clipped) > interior(&escapes));
+
+        request.options.limit = 20_000;
+
+        let image = request.render().unwrap();
+        let mut grays: Vec<u8> = image.pixels.chunks(3).map(|pixel| pixel[0]).collect();
+        assert!(grays.contains(&255) && grays.contains(&0));
+        grays.sort();
+        grays.dedup();
+        assert!(grays.len() > 20, "{} levels", grays.len());
+    }
+
+    #[test]
+    fn adaptive_sampling_refines_only_edges() {
+        let mut request = RenderRequest::new(
+            (32, 24),
+            Complex { re: -2.5, im: 1.2 },
+            Complex { re: 1.0, im: -1.2 },
+        );
+        request.options.sampling = Sampling::Grid(3);
+        let grid = request.escapes().unwrap();
+        request.options.sampling = Sampling::Adaptive(3);
+        let adaptive = request.escapes().unwrap();
+
+        let mut refined = 0;
+        for (grid, adaptive) in grid.chunks(9).zip(adaptive.chunks(9)) {
+            if adaptive.iter().all(|&sample| sample == adaptive[0]) {
+                assert_eq!(adaptive[0], grid[4]);
+            } else {
+                assert_eq!(adaptive, grid);
+                refined += 1;
+            }
+        }
+        assert!(refined > 0 && refined < 32 * 24 / 2, "{} refined", refined);
+    }
+
+    #[test]
+    fn layers_match_separate_renders() {
+        let mut request = RenderRequest::new(
+            (24, 18),
+            Complex { re: -2.5, im: 1.2 },
+            Complex { re: 1.0, im: -1.2 },
+        );
+        request.rotation = Rotation::new(Complex { re: -0.75, im: 0.0 }, 20.0);
+        let scheduler = TileScheduler::new();
+        for &(coloring, other) in [
+            (Coloring::Integer, Coloring::Smooth),
+            (Coloring::Distance, Coloring::Integer),
+        ]
+        .iter()
+        {
+            for &(sampling, vectorize) in
+                [(Sampling::Single, true), (Sampling::Grid(2), false)].iter()
+            {
+                request.options.coloring = coloring;
+                request.options.sampling = sampling;
+                request.options.vectorize = vectorize;
+                let (escapes, others) = request.layers_with(other, &scheduler).unwrap();
+                assert_eq!(escapes, request.escapes().unwrap());
+
+                let mut separate = request.clone();
+                separate.options.coloring = other;
+                assert_eq!(others, separate.escapes().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn precise_requests_reject_what_they_cannot_render() {
+        let mut request = RenderRequest::new(
+            (8, 6),
+            Complex { re: -2.0, im: 1.0 },
+            Complex { re: 1.0, im: -1.0 },
+        );
+        request.precision = Precision::DoubleDouble;
+        request.center = Some(Center {
+            text: "-0.5;0".to_string(),
+            width: 3.0,
+        });
+        assert_eq!(
+            request.escapes().unwrap_err().to_string(),
+            "malformed center `-0.5;0`"
+        );
+
+        request.center = None;
+        assert!(request.escapes().is_ok());
+        request.options.fill = true;
+        assert_eq!(
+            request.escapes().unwrap_err().to_string(),
+            "dd renders cannot be filled or adaptively supersampled"
+        );
+        request.options.fill = false;
+        request.options.sampling = Sampling::Adaptive(2);
+        assert!(request.escapes().is_err());
+        assert!(request
+            .layers_with(Coloring::Smooth, &TileScheduler::new())
+            .is_err());
+
+        request.precision = Precision::Deep;
+        request.center = Some(Center {
+            text: "nowhere".to_string(),
+            width: 3.0,
+        });
+        assert!(request.escapes().is_err());
+    }
+
+    #[test]
+    fn unknown_palette_is_an_error() {
+        let mut request = RenderRequest::new(
+            (4, 3),
+            Complex { re: -2.0, im: 1.0 },
+            Complex { re: 1.0, im: -1.0 },
+        );
+        request.options.palette = "no-such-palette".to_string();
+        assert!(request.render().is_err());
+    }
+
+    #[test]
+    fn fixed_range_keeps_colors_across_frames() {
+        let palette = Palette::builtin("fire").unwrap();
+        let options = Options {
+            limit: 255,
+            ..Options::default()
+        };
+        let near = [Some(10.0), Some(20.0), Some(30.0)];
+        let deep = [Some(10.0), Some(20.0), Some(200.0)];
+        let color = |escapes: &[Option<f32>]| {
+            ImageBuffer::from_escapes_in(escapes, (3, 1), &palette, options.fixed_range()).pixels
+                [3..6]
+                .to_vec()
+        };
+        assert_eq!(color(&near), color(&deep));
+        assert_ne!(
+            ImageBuffer::from_escapes(&near, (3, 1), &palette).pixels[3..6],
+            ImageBuffer::from_escapes(&deep, (3, 1), &palette).pixels[3..6]
+        );
+    }
+}
@@ -0,0 +1,70 @@
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Sampling {
+    Single,
+    Grid(usize),
+    Jitter(usize),
+    Adaptive(usize),
+}
+
+impl Sampling {
+    pub fn samples(self) -> usize {
+        match self {
+            Sampling::Single => 1,
+            Sampling::Grid(n) | Sampling::Jitter(n) | Sampling::Adaptive(n) => n * n,
+        }
+    }
+
+    pub fn offset(self, index: usize, seed: u64) -> (f64, f64) {
+        match self {
+            Sampling::Single => (0.0, 0.0),
+            Sampling::Grid(n) | Sampling::Adaptive(n) => (
+                ((index % n) as f64 + 0.5) / n as f64,
+                ((index / n) as f64 + 0.5) / n as f64,
+            ),
+            Sampling::Jitter(n) => (
+                ((index % n) as f64 + unit_hash(seed, 2 * index as u64)) / n as f64,
+                ((index / n) as f64 + unit_hash(seed, 2 * index as u64 + 1)) / n as f64,
+            ),
+        }
+    }
+}
+
+pub(crate) fn unit_hash(seed: u64, index: u64) -> f64 {
+    let mut x = seed ^ index.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^= x >> 31;
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_samples_sit_at_cell_centers() {
+        let grid = Sampling::Grid(2);
+        let offsets: Vec<(f64, f64)> = (0..grid.samples())
+            .map(|index| grid.offset(index, 7))
+            .collect();
+        assert_eq!(
+            offsets,
+            vec![(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]
+        );
+        assert_eq!(Sampling::Adaptive(2).offset(3, 0), (0.75, 0.75));
+        assert_eq!(Sampling::Single.samples(), 1);
+    }
+
+    #[test]
+    fn jittered_samples_stay_in_their_cells() {
+        let jitter = Sampling::Jitter(3);
+        for index in 0..jitter.samples() {
+            let (x, y) = jitter.offset(index, 42);
+            let (column, row) = ((index % 3) as f64 / 3.0, (index / 3) as f64 / 3.0);
+            assert!(x >= column && x < column + 1.0 / 3.0);
+            assert!(y >= row && y < row + 1.0 / 3.0);
+            assert_eq!(jitter.offset(index, 42), (x, y));
+            assert_ne!(jitter.offset(index, 43), (x, y));
+        }
+    }
+}
@@ -0,0 +1,578 @@
+use super::{
+    frame_corners, Coloring, Format, Fractal, Options, Precision, RenderRequest, Rotation,
+};
+use num::Complex;
+use std::io;
+use std::iter::Peekable;
+use std::str::Chars;
+
+const DEFAULT_WIDTH: f64 = 4.0;
+const TOML_SUBSET: &str = "scene files support a subset of TOML: `#` comments, `[[scene]]` \
+                           tables and one-line `key = value` entries whose values are \
+                           double-quoted strings, numbers, booleans or one-line arrays";
+
+#[derive(Clone, PartialEq, Debug)]
+enum Value {
+    Text(String),
+    Number(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+    Table(Vec<(String, Value)>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Scene {
+    pub output: String,
+    pub format: Format,
+    pub request: RenderRequest,
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    json: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str, json: bool) -> Parser<'a> {
+        Parser {
+            chars: text.chars().peekable(),
+            json,
+        }
+    }
+
+    fn skip_space(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_space();
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("expected `{}`, found `{}`", expected, c)),
+            None => Err(format!("expected `{}`, found the end", expected)),
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        self.skip_space();
+        match self.chars.next() {
+            None | Some('#') => Ok(()),
+            Some(c) => Err(format!("unexpected `{}`", c)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_space();
+        match self.chars.peek() {
+            Some('"') => self.text().map(Value::Text),
+            Some('[') => self.sequence('[', ']', Parser::value).map(Value::Array),
+            Some('{') => self.sequence('{', '}', Parser::entry).map(Value::Table),
+            Some(_) => {
+                let mut word = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if !(c.is_alphanumeric() || "+-._".contains(c)) {
+                        break;
+                    }
+                    word.push(c);
+                    self.chars.next();
+                }
+                let number = if self.json {
+                    Some(word.clone()).filter(|word| json_number(word))
+                } else {
+                    toml_number(&word)
+                };
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => number
+                        .and_then(|number| number.parse().ok())
+                        .map(Value::Number)
+                        .ok_or_else(|| {
+                            format!("`{}` is not a string, number, boolean or array", word)
+                        }),
+                }
+            }
+            None => Err("expected a value, found the end".to_string()),
+        }
+    }
+
+    fn text(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(text),
+                Some('\\') => match self.chars.next() {
+                    Some('n') => text.push('\n'),
+                    Some('t') => text.push('\t'),
+                    Some(c @ '"') | Some(c @ '\\') | Some(c @ '/') => text.push(c),
+                    _ => return Err("unsupported escape in string".to_string()),
+                },
+                Some(c) => text.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    fn entry(&mut self) -> Result<(String, Value), String> {
+        let key = self.text()?;
+        self.expect(':')?;
+        Ok((key, self.value()?))
+    }
+
+    fn sequence<T>(
+        &mut self,
+        open: char,
+        close: char,
+        item: fn(&mut Parser<'a>) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        self.expect(open)?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_space();
+            if self.chars.peek() == Some(&close) {
+                self.chars.next();
+                return Ok(items);
+            }
+            items.push(item(self)?);
+            self.skip_space();
+            match self.chars.next() {
+                Some(',') => {}
+                Some(c) if c == close => return Ok(items),
+                _ => return Err(format!("expected `,` or `{}`", close)),
+            }
+        }
+    }
+}
+
+fn json_number(word: &str) -> bool {
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let word = word.strip_prefix('-').unwrap_or(word);
+    let (mantissa, exponent) = match word.find(['e', 'E']) {
+        Some(index) => (&word[..index], Some(&word[index + 1..])),
+        None => (word, None),
+    };
+    let (integer, fraction) = match mantissa.find('.') {
+        Some(index) => (&mantissa[..index], Some(&mantissa[index + 1..])),
+        None => (mantissa, None),
+    };
+    digits(integer)
+        && (integer == "0" || !integer.starts_with('0'))
+        && fraction.is_none_or(digits)
+        && exponent
+            .is_none_or(|exponent| digits(exponent.strip_prefix(['+', '-']).unwrap_or(exponent)))
+}
+
+fn toml_number(word: &str) -> Option<String> {
+    let bytes = word.as_bytes();
+    let separated = bytes.iter().enumerate().all(|(index, &b)| {
+        b != b'_'
+            || (index > 0
+                && bytes[index - 1].is_ascii_digit()
+                && bytes.get(index + 1).is_some_and(u8::is_ascii_digit))
+    });
+    Some(word.replace('_', "")).filter(|_| separated)
+}
+
+fn parse_toml(text: &str) -> Result<Value, String> {
+    let mut defaults = Vec::new();
+    let mut scenes = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let at_line = |reason: String| format!("line {}: {}; {}", number + 1, reason, TOML_SUBSET);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            let header = line.split('#').next().unwrap_or("").trim();
+            if header != "[[scene]]" {
+                return Err(at_line(format!("unknown table `{}`", header)));
+            }
+            scenes.push(Value::Table(Vec::new()));
+            continue;
+        }
+
+        let index = line
+            .find('=')
+            .ok_or_else(|| at_line("expected `key = value`".to_string()))?;
+        let key = line[..index].trim().trim_matches('"').to_string();
+        let rest = line[index + 1..].trim_start();
+        if rest.starts_with('{') {
+            return Err(at_line("inline tables are not supported".to_string()));
+        }
+        if rest.starts_with('\'') {
+            return Err(at_line(
+                "single-quoted strings are not supported".to_string(),
+            ));
+        }
+        let mut parser = Parser::new(rest, false);
+        let value = parser.value().map_err(at_line)?;
+        parser.finish().map_err(at_line)?;
+        match scenes.last_mut() {
+            Some(Value::Table(entries)) => entries.push((key, value)),
+            _ => defaults.push((key, value)),
+        }
+    }
+
+    defaults.push(("scene".to_string(), Value::Array(scenes)));
+    Ok(Value::Table(defaults))
+}
+
+fn parse_json(text: &str) -> Result<Value, String> {
+    let mut parser = Parser::new(text, true);
+    let value = parser.value()?;
+    parser.skip_space();
+    match parser.chars.next() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected `{}` after the document", c)),
+    }
+}
+
+fn number(key: &str, value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Number(number) if number.is_finite() => Ok(*number),
+        _ => Err(format!("`{}` must be a number", key)),
+    }
+}
+
+fn positive(key: &str, value: &Value) -> Result<f64, String> {
+    match number(key, value)? {
+        number if number > 0.0 => Ok(number),
+        _ => Err(format!("`{}` must be positive", key)),
+    }
+}
+
+fn integer(key: &str, value: &Value) -> Result<u32, String> {
+    match positive(key, value)? {
+        number if number.fract() == 0.0 && number <= u32::MAX as f64 => Ok(number as u32),
+        _ => Err(format!("`{}` must be a positive integer", key)),
+    }
+}
+
+fn text<'v>(key: &str, value: &'v Value) -> Result<&'v str, String> {
+    match value {
+        Value::Text(text) => Ok(text),
+        _ => Err(format!("`{}` must be a string", key)),
+    }
+}
+
+fn pair(key: &str, value: &Value) -> Result<(f64, f64), String> {
+    match value {
+        Value::Array(items) if items.len() == 2 => {
+            Ok((number(key, &items[0])?, number(key, &items[1])?))
+        }
+        _ => Err(format!("`{}` must be an array of two numbers", key)),
+    }
+}
+
+fn point(key: &str, value: &Value) -> Result<Complex<f64>, String> {
+    pair(key, value).map(|(re, im)| Complex { re, im })
+}
+
+fn build_scene(entries: &[(String, Value)]) -> Result<Scene, String> {
+    let mut output = None;
+    let mut format = None;
+    let mut bounds = (800, 600);
+    let mut kind = "mandelbrot";
+    let mut c = None;
+    let mut degree = None;
+    let mut upper_left = None;
+    let mut lower_right = None;
+    let mut center = None;
+    let mut width = None;
+    let mut zoom = None;
+    let mut degrees = 0.0;
+    let mut options = Options::default();
+
+    for (key, value) in entries {
+        let key = key.as_str();
+        match key {
+            "output" => output = Some(text(key, value)?.to_string()),
+            "format" => {
+                let name = text(key, value)?;
+                format = Some(
+                    Format::from_name(name).ok_or_else(|| format!("unknown format `{}`", name))?,
+                )
+            }
+            "size" => {
+                let (width, height) = pair(key, value)?;
+                if width < 1.0 || height < 1.0 || width.fract() != 0.0 || height.fract() != 0.0 {
+                    return Err("`size` must be two positive integers".to_string());
+                }
+                bounds = (width as usize, height as usize);
+            }
+            "fractal" => kind = text(key, value)?,
+            "c" => c = Some(point(key, value)?),
+            "degree" => degree = Some(integer(key, value)?),
+            "upper_left" => upper_left = Some(point(key, value)?),
+            "lower_right" => lower_right = Some(point(key, value)?),
+            "center" => center = Some(point(key, value)?),
+            "width" => width = Some(positive(key, value)?),
+            "zoom" => zoom = Some(positive(key, value)?),
+            "rotate" => degrees = number(key, value)?,
+            "iterations" => options.limit = integer(key, value)?,
+            "palette" => options.palette = text(key, value)?.to_string(),
+            "coloring" => {
+                let name = text(key, value)?;
+                options.coloring = Coloring::from_name(name)
+                    .ok_or_else(|| format!("unknown coloring `{}`", name))?
+            }
+            "equalize" | "fill" => {
+                let flag = match value {
+                    Value::Bool(flag) => *flag,
+                    _ => return Err(format!("`{}` must be true or false", key)),
+                };
+                if key == "equalize" {
+                    options.equalize = flag;
+                } else {
+                    options.fill = flag;
+                }
+            }
+            "scene" => {}
+            _ => return Err(format!("unknown key `{}`", key)),
+        }
+    }
+
+    let fractal = match (kind, c, degree) {
+        ("mandelbrot", None, None) => Fractal::Mandelbrot,
+        ("julia", Some(c), None) => Fractal::Julia(c),
+        ("multibrot", None, Some(degree)) if degree >= 2 => Fractal::Multibrot(degree),
+        ("burning-ship", None, None) => Fractal::BurningShip,
+        ("tricorn", None, None) => Fractal::Tricorn,
+        ("julia", None, _) => return Err("julia scenes need `c`".to_string()),
+        ("multibrot", _, _) => {
+            return Err("multibrot scenes need a `degree` of at least 2".to_string())
+        }
+        ("mandelbrot", _, _) | ("julia", _, _) | ("burning-ship", _, _) | ("tricorn", _, _) => {
+            return Err(format!("`c` and `degree` do not apply to {}", kind))
+        }
+        _ => return Err(format!("unknown fractal `{}`", kind)),
+    };
+    if options.coloring == Coloring::Distance && !fractal.supports_distance() {
+        return Err(format!("distance coloring does not apply to {}", kind));
+    }
+
+    let (upper_left, lower_right) = match (upper_left, lower_right, center) {
+        (Some(upper_left), Some(lower_right), None) if width.is_none() && zoom.is_none() => {
+            (upper_left, lower_right)
+        }
+        (None, None, center) if width.is_none() || zoom.is_none() => {
+            let center = center.unwrap_or(Complex { re: -0.75, im: 0.0 });
+            let width = width.unwrap_or(DEFAULT_WIDTH / zoom.unwrap_or(1.0));
+            frame_corners(center, width, bounds)
+        }
+        _ => {
+            return Err(
+                "give the view as `upper_left` and `lower_right`, or as `center` with `width` or `zoom`"
+                    .to_string(),
+            )
+        }
+    };
+
+    let output = output.ok_or_else(|| "every scene needs an `output`".to_string())?;
+    let format = format
+        .or_else(|| Format::from_extension(&output))
+        .unwrap_or(Format::Png);
+    Ok(Scene {
+        output,
+        format,
+        request: RenderRequest {
+            fractal,
+            bounds,
+            upper_left,
+            lower_right,
+            rotation: Rotation::new((upper_left + lower_right) / 2.0, degrees),
+            precision: Precision::Double,
+            center: None,
+            options,
+        },
+    })
+}
+
+pub fn parse_scenes(text: &str, json: bool) -> Result<Vec<Scene>, String> {
+    let document = if json {
+        parse_json(text)?
+    } else {
+        parse_toml(text)?
+    };
+    let defaults = match document {
+        Value::Table(entries) => entries,
+        _ => return Err("the scene file must be a table of settings and scenes".to_string()),
+    };
+    let scenes = match defaults.iter().find(|(key, _)| key == "scene") {
+        Some((_, Value::Array(scenes))) if !scenes.is_empty() => scenes,
+        _ => return Err("the scene file holds no scenes".to_string()),
+    };
+
+    scenes
+        .iter()
+        .enumerate()
+        .map(|(index, scene)| {
+            let entries = match scene {
+                Value::Table(entries) => entries,
+                _ => return Err(format!("scene {}: expected a table", index + 1)),
+            };
+            let merged: Vec<(String, Value)> = defaults.iter().chain(entries).cloned().collect();
+            build_scene(&merged).map_err(|reason| format!("scene {}: {}", index + 1, reason))
+        })
+        .collect()
+}
+
+pub fn read_scenes(filename: &str) -> Result<Vec<Scene>, io::Error> {
+    let text = std::fs::read_to_string(filename)?;
+    parse_scenes(&text, filename.ends_with(".json")).map_err(|reason| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", filename, reason),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"
+# shared by every scene
+size = [160, 120]
+iterations = 500
+palette = "fire"
+
+[[scene]]
+output = "full.png"
+
+[[scene]]
+output = "seahorse.ppm"
+fractal = "julia"
+c = [-0.8, 0.156]
+center = [0.0, 0.0]
+zoom = 1.5
+coloring = "smooth"
+iterations = 1_000  # overrides the default
+"#;
+
+    const JSON: &str = r#"{
+        "size": [160, 120],
+        "iterations": 500,
+        "palette": "fire",
+        "scene": [
+            {"output": "full.png"},
+            {
+                "output": "seahorse.ppm", "fractal": "julia", "c": [-0.8, 0.156],
+                "center": [0.0, 0.0], "zoom": 1.5, "coloring": "smooth", "iterations": 1000
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn toml_and_json_describe_the_same_scenes() {
+        let toml = parse_scenes(TOML, false).unwrap();
+        let json = parse_scenes(JSON, true).unwrap();
+        assert_eq!(toml.len(), 2);
+        for (toml, json) in toml.iter().zip(&json) {
+            assert_eq!(toml.output, json.output);
+            assert_eq!(toml.format, json.format);
+            assert_eq!(format!("{:?}", toml.request), format!("{:?}", json.request));
+        }
+
+        let full = &toml[0];
+        assert_eq!(full.format, Format::Png);
+        assert_eq!(full.request.bounds, (160, 120));
+        assert_eq!(full.request.options.limit, 500);
+        assert_eq!(full.request.upper_left, Complex { re: -2.75, im: 1.5 });
+
+        let julia = &toml[1];
+        assert_eq!(julia.format, Format::Ppm);
+        assert_eq!(
+            julia.request.fractal,
+            Fractal::Julia(Complex {
+                re: -0.8,
+                im: 0.156
+            })
+        );
+        assert_eq!(julia.request.options.limit, 1000);
+        assert_eq!(julia.request.options.coloring, Coloring::Smooth);
+        assert_eq!(julia.request.options.palette, "fire");
+    }
+
+    #[test]
+    fn mistakes_name_the_scene_and_line() {
+        let error = |text: &str, json| parse_scenes(text, json).unwrap_err();
+        assert_eq!(
+            error("[[scene]]\noutput = \"a.png\"\nzoom = [1]", false),
+            "scene 1: `zoom` must be a number"
+        );
+        assert_eq!(
+            error("[[scene]]\noutput = a.png", false),
+            format!(
+                "line 2: `a.png` is not a string, number, boolean or array; {}",
+                TOML_SUBSET
+            )
+        );
+        assert_eq!(
+            error("[scene]\noutput = \"a.png\"", false),
+            format!("line 1: unknown table `[scene]`; {}", TOML_SUBSET)
+        );
+        assert_eq!(
+            error("[[scene]]\noutput = 'a.png'", false),
+            format!(
+                "line 2: single-quoted strings are not supported; {}",
+                TOML_SUBSET
+            )
+        );
+        assert_eq!(
+            error("[[scene]]\nc = { re = 0, im = 1 }", false),
+            format!("line 2: inline tables are not supported; {}", TOML_SUBSET)
+        );
+        assert_eq!(
+            error("[[scene]]\nsize = [\n  4,\n  4,\n]", false),
+            format!("line 2: expected a value, found the end; {}", TOML_SUBSET)
+        );
+        assert_eq!(
+            error(
+                r#"{"scene": [{"output": "a.png"}, {"output": "b.png", "iteration": 9}]}"#,
+                true
+            ),
+            "scene 2: unknown key `iteration`"
+        );
+        assert_eq!(
+            error("[[scene]]\nsize = [4, 4]", false),
+            "scene 1: every scene needs an `output`"
+        );
+        assert_eq!(
+            error("[[scene]]\noutput = \"a.png\"\niterations = 0.5", false),
+            "scene 1: `iterations` must be a positive integer"
+        );
+        assert_eq!(
+            error(
+                "[[scene]]\noutput = \"a.png\"\nfractal = \"multibrot\"\ndegree = 2.9",
+                false
+            ),
+            "scene 1: `degree` must be a positive integer"
+        );
+        assert_eq!(
+            error("iterations = 9", false),
+            "the scene file holds no scenes"
+        );
+        assert_eq!(
+            error("[[scene]]\noutput = \"a.png\"\niterations = 1__000", false),
+            format!(
+                "line 3: `1__000` is not a string, number, boolean or array; {}",
+                TOML_SUBSET
+            )
+        );
+        for number in &["1_000", "+1", "01", ".5", "1.", "1e", "inf", "NaN"] {
+            let text = format!(r#"{{"iterations": {}, "scene": []}}"#, number);
+            assert_eq!(
+                error(&text, true),
+                format!("`{}` is not a string, number, boolean or array", number)
+            );
+        }
+        assert_eq!(
+            error(r#"{"scene": [{"output": "a.png"}] ]"#, true),
+            "expected `,` or `}`"
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use super::Region;
+use std::sync::Mutex;
+use std::thread::ThreadId;
+use std::time::{Duration, Instant};
+
+const TILE_SIZE: usize = 64;
+const MIN_TILE_SIZE: usize = 8;
+const SPLIT_COST: Duration = Duration::from_millis(2);
+
+struct WorkerStats {
+    thread: ThreadId,
+    tiles: usize,
+    pixels: usize,
+    busy: Duration,
+}
+
+pub struct TileScheduler {
+    workers: Mutex<Vec<WorkerStats>>,
+    wall: Mutex<Duration>,
+}
+
+impl Default for TileScheduler {
+    fn default() -> TileScheduler {
+        TileScheduler::new()
+    }
+}
+
+type Rows<'a> = Vec<&'a mut [Option<f32>]>;
+
+impl TileScheduler {
+    pub fn new() -> TileScheduler {
+        TileScheduler {
+            workers: Mutex::new(Vec::new()),
+            wall: Mutex::new(Duration::from_secs(0)),
+        }
+    }
+
+    pub fn render<F>(&self, escapes: &mut [Option<f32>], region: Region, render_tile: F)
+    where
+        F: Fn(&mut [Option<f32>], Region) + Sync,
+    {
+        if region.pixels() == 0 {
+            return;
+        }
+        let samples = escapes.len() / region.pixels();
+        assert!(escapes.len() == region.pixels() * samples);
+
+        let start = Instant::now();
+        let rows: Rows = escapes.chunks_mut(region.width * samples).collect();
+        self.schedule(rows, region, samples, &render_tile);
+        *self.wall.lock().unwrap() += start.elapsed();
+    }
+
+    fn schedule<F>(&self, rows: Rows, region: Region, samples: usize, render_tile: &F)
+    where
+        F: Fn(&mut [Option<f32>], Region) + Sync,
+    {
+        if region.width > TILE_SIZE || region.height > TILE_SIZE {
+            let ((first, first_region), (second, second_region)) =
+                split_region(rows, region, samples);
+            rayon::join(
+                || self.schedule(first, first_region, samples, render_tile),
+                || self.schedule(second, second_region, samples, render_tile),
+            );
+            return;
+        }
+
+        let ((probe, probe_region), (rest, rest_region)) = split_rows(rows, region, 1);
+        let elapsed = self.render_tile(probe, probe_region, samples, render_tile);
+        if rest_region.height == 0 {
+            return;
+        }
+
+        let estimate = elapsed * rest_region.height as u32;
+        if estimate > SPLIT_COST && rest_region.width.max(rest_region.height) > MIN_TILE_SIZE {
+            let ((first, first_region), (second, second_region)) =
+                split_region(rest, rest_region, samples);
+            rayon::join(
+                || self.schedule(first, first_region, samples, render_tile),
+                || self.schedule(second, second_region, samples, render_tile),
+            );
+        } else {
+            self.render_tile(rest, rest_region, samples, render_tile);
+        }
+    }
+
+    fn render_tile<F>(
+        &self,
+        mut rows: Rows,
+        region: Region,
+        samples: usize,
+        render_tile: &F,
+    ) -> Duration
+    where
+        F: Fn(&mut [Option<f32>], Region) + Sync,
+    {
+        let start = Instant::now();
+        let row_length = region.width * samples;
+        let mut escapes = vec![None; region.pixels() * samples];
+        render_tile(&mut escapes, region);
+        for (row, output) in rows.iter_mut().enumerate() {
+            output.copy_from_slice(&escapes[row * row_length..(row + 1) * row_length]);
+        }
+        let elapsed = start.elapsed();
+
+        let thread = std::thread::current().id();
+        let mut workers = self.workers.lock().unwrap();
+        let index = match workers.iter().position(|worker| worker.thread == thread) {
+            Some(index) => index,
+            None => {
+                workers.push(WorkerStats {
+                    thread,
+                    tiles: 0,
+                    pixels: 0,
+                    busy: Duration::from_secs(0),
+                });
+                workers.len() - 1
+            }
+        };
+        workers[index].tiles += 1;
+        workers[index].pixels += region.pixels();
+        workers[index].busy += elapsed;
+
+        elapsed
+    }
+
+    pub fn report(&self) -> String {
+        let workers = self.workers.lock().unwrap();
+        let wall = *self.wall.lock().unwrap();
+        let mut report = format!(
+            "{:>6} {:>8} {:>10} {:>10} {:>7}\n",
+            "worker", "tiles", "pixels", "busy ms", "busy %"
+        );
+        let wall_ms = wall.as_secs_f64() * 1000.0;
+        let mut busy = Duration::from_secs(0);
+        for (index, worker) in workers.iter().enumerate() {
+            let busy_ms = worker.busy.as_secs_f64() * 1000.0;
+            report.push_str(&format!(
+                "{:>6} {:>8} {:>10} {:>10.1} {:>6.1}%\n",
+                index,
+                worker.tiles,
+                worker.pixels,
+                busy_ms,
+                100.0 * busy_ms / wall_ms.max(1e-9)
+            ));
+            busy += worker.busy;
+        }
+        report.push_str(&format!(
+            "wall {:.1} ms, busy {:.1} ms across {} threads",
+            wall_ms,
+            busy.as_secs_f64() * 1000.0,
+            workers.len()
+        ));
+        report
+    }
+}
+
+fn split_region(rows: Rows, region: Region, samples: usize) -> ((Rows, Region), (Rows, Region)) {
+    if region.width >= region.height {
+        split_columns(rows, region, samples, region.width / 2)
+    } else {
+        split_rows(rows, region, region.height / 2)
+    }
+}
+
+fn split_rows(mut rows: Rows, region: Region, at: usize) -> ((Rows, Region), (Rows, Region)) {
+    let rest = rows.split_off(at);
+    let first = Region {
+        height: at,
+        ..region
+    };
+    let second = Region {
+        top: region.top + at,
+        height: region.height - at,
+        ..region
+    };
+    ((rows, first), (rest, second))
+}
+
+fn split_columns(
+    rows: Rows,
+    region: Region,
+    samples: usize,
+    at: usize,
+) -> ((Rows, Region), (Rows, Region)) {
+    let mut left = Vec::with_capacity(rows.len());
+    let mut right = Vec::with_capacity(rows.len());
+    for row in rows {
+        let (first, second) = row.split_at_mut(at * samples);
+        left.push(first);
+        right.push(second);
+    }
+    let first = Region {
+        width: at,
+        ..region
+    };
+    let second = Region {
+        left: region.left + at,
+        width: region.width - at,
+        ..region
+    };
+    ((left, first), (right, second))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{render, Coloring, Fractal, Options, Rotation, Sampling, View};
+    use super::*;
+    use num::Complex;
+    use rayon::{Configuration, ThreadPool};
+
+    fn pool(threads: usize) -> ThreadPool {
+        ThreadPool::new(Configuration::new().set_num_threads(threads)).unwrap()
+    }
+
+    fn tag(x: usize, y: usize, sample: usize) -> Option<f32> {
+        Some((y * 1000 + x) as f32 + sample as f32 / 10.0)
+    }
+
+    #[test]
+    fn every_pixel_of_an_odd_region_is_rendered_once() {
+        let region = Region {
+            left: 5,
+            top: 3,
+            width: 37,
+            height: 23,
+        };
+        let samples = 2;
+        let scheduler = TileScheduler::new();
+        let visits = Mutex::new(vec![0; region.pixels()]);
+        let mut escapes = vec![None; region.pixels() * samples];
+        pool(4).install(|| {
+            scheduler.render(&mut escapes, region, |tile, tile_region| {
+                std::thread::sleep(Duration::from_micros(200));
+                let mut visits = visits.lock().unwrap();
+                for row in 0..tile_region.height {
+                    for column in 0..tile_region.width {
+                        let (x, y) = (tile_region.left + column, tile_region.top + row);
+                        visits[(y - region.top) * region.width + x - region.left] += 1;
+                        for sample in 0..samples {
+                            let index = (row * tile_region.width + column) * samples + sample;
+                            tile[index] = tag(x, y, sample);
+                        }
+                    }
+                }
+            })
+        });
+
+        let workers = scheduler.workers.lock().unwrap();
+        assert!(workers.iter().map(|worker| worker.tiles).sum::<usize>() > 8);
+        assert!(visits.into_inner().unwrap().iter().all(|&count| count == 1));
+        for (index, escape) in escapes.iter().enumerate() {
+            let (pixel, sample) = (index / samples, index % samples);
+            let (x, y) = (
+                region.left + pixel % region.width,
+                region.top + pixel / region.width,
+            );
+            assert_eq!(*escape, tag(x, y, sample));
+        }
+    }
+
+    #[test]
+    fn scheduled_renders_match_a_single_threaded_render() {
+        let bounds = (150, 97);
+        let view = View {
+            bounds,
+            upper_left: Complex { re: -2.2, im: 1.2 },
+            lower_right: Complex { re: 0.8, im: -1.2 },
+            rotation: Rotation::new(Complex { re: -0.7, im: 0.0 }, 15.0),
+        };
+        let options = Options {
+            coloring: Coloring::Smooth,
+            sampling: Sampling::Grid(2),
+            limit: 300,
+            ..Options::default()
+        };
+        let region = Region::full(bounds);
+        let mut expected = vec![None; region.pixels() * 4];
+        render(&mut expected, view, region, Fractal::Mandelbrot, &options);
+
+        for &threads in [1, 4].iter() {
+            let mut escapes = vec![None; region.pixels() * 4];
+            pool(threads).install(|| {
+                TileScheduler::new().render(&mut escapes, region, |tile, tile_region| {
+                    render(tile, view, tile_region, Fractal::Mandelbrot, &options)
+                })
+            });
+            assert!(escapes == expected, "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn statistics_count_every_tile_and_pixel() {
+        let region = Region::full((130, 70));
+        let scheduler = TileScheduler::new();
+        let calls = Mutex::new(0);
+        let mut escapes = vec![None; region.pixels()];
+        for _ in 0..2 {
+            pool(3).install(|| {
+                scheduler.render(&mut escapes, region, |_, _| *calls.lock().unwrap() += 1)
+            });
+        }
+
+        let workers = scheduler.workers.lock().unwrap();
+        let tiles: usize = workers.iter().map(|worker| worker.tiles).sum();
+        let pixels: usize = workers.iter().map(|worker| worker.pixels).sum();
+        assert_eq!(tiles, *calls.lock().unwrap());
+        assert!(tiles > 2);
+        assert_eq!(pixels, region.pixels() * 2);
+        let threads = workers.len();
+        drop(workers);
+
+        let report = scheduler.report();
+        assert_eq!(report.lines().count(), threads + 2);
+        assert!(report.ends_with(&format!("across {} threads", threads)));
+    }
+}
@@ -0,0 +1,277 @@
+use super::{escape_orbit, escape_value, orbit_value, Coloring, Fractal};
+use num::Complex;
+
+pub const LANES: usize = 4;
+
+pub fn supports(fractal: Fractal) -> bool {
+    match fractal {
+        Fractal::Mandelbrot | Fractal::Julia(_) => available(),
+        _ => false,
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn available() -> bool {
+    is_x86_feature_detected!("avx")
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn available() -> bool {
+    false
+}
+
+pub fn escape_orbits(
+    fractal: Fractal,
+    points: &[Complex<f64>],
+    limit: u32,
+    orbits: &mut [Option<(u32, f64)>],
+) {
+    assert!(points.len() == orbits.len());
+
+    let lanes = if supports(fractal) {
+        points.len() / LANES * LANES
+    } else {
+        0
+    };
+    for (points, orbits) in points[..lanes]
+        .chunks(LANES)
+        .zip(orbits[..lanes].chunks_mut(LANES))
+    {
+        let mut group = [Complex { re: 0.0, im: 0.0 }; LANES];
+        group.copy_from_slice(points);
+        orbits.copy_from_slice(&escape_lanes(fractal, group, limit));
+    }
+    for (point, orbit) in points[lanes..].iter().zip(&mut orbits[lanes..]) {
+        *orbit = escape_orbit(fractal, *point, limit);
+    }
+}
+
+pub fn escape_values(
+    fractal: Fractal,
+    points: &[Complex<f64>],
+    limit: u32,
+    coloring: Coloring,
+    escapes: &mut [Option<f32>],
+) {
+    if !supports(fractal) || coloring == Coloring::Distance {
+        for (point, escape) in points.iter().zip(escapes) {
+            *escape = escape_value(fractal, *point, limit, coloring).map(|v| v as f32);
+        }
+        return;
+    }
+
+    let mut orbits = vec![None; points.len()];
+    escape_orbits(fractal, points, limit, &mut orbits);
+    for (orbit, escape) in orbits.into_iter().zip(escapes) {
+        *escape = orbit_value(fractal, orbit, coloring).map(|v| v as f32);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn escape_lanes(
+    fractal: Fractal,
+    points: [Complex<f64>; LANES],
+    limit: u32,
+) -> [Option<(u32, f64)>; LANES] {
+    let mut z = [Complex { re: 0.0, im: 0.0 }; LANES];
+    let mut c = [Complex { re: 0.0, im: 0.0 }; LANES];
+    let mut active = 0;
+    for lane in 0..LANES {
+        let (start, constant) = fractal.start(points[lane]);
+        z[lane] = start;
+        c[lane] = constant;
+        if !fractal.in_known_interior(points[lane]) {
+            active |= 1 << lane;
+        }
+    }
+
+    // SAFETY: escape_orbits only calls this when supports() holds, which requires the
+    // runtime is_x86_feature_detected!("avx") check in available() to pass.
+    unsafe { escape_lanes_avx(z, c, active, limit) }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn escape_lanes(
+    fractal: Fractal,
+    points: [Complex<f64>; LANES],
+    limit: u32,
+) -> [Option<(u32, f64)>; LANES] {
+    let mut orbits = [None; LANES];
+    for (point, orbit) in points.iter().zip(orbits.iter_mut()) {
+        *orbit = escape_orbit(fractal, *point, limit);
+    }
+    orbits
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx")]
+unsafe fn escape_lanes_avx(
+    z: [Complex<f64>; LANES],
+    c: [Complex<f64>; LANES],
+    mut active: i32,
+    limit: u32,
+) -> [Option<(u32, f64)>; LANES] {
+    use std::arch::x86_64::*;
+
+    let mut orbits = [None; LANES];
+    if active == 0 {
+        return orbits;
+    }
+
+    let mut z_re = _mm256_setr_pd(z[0].re, z[1].re, z[2].re, z[3].re);
+    let mut z_im = _mm256_setr_pd(z[0].im, z[1].im, z[2].im, z[3].im);
+    let c_re = _mm256_setr_pd(c[0].re, c[1].re, c[2].re, c[3].re);
+    let c_im = _mm256_setr_pd(c[0].im, c[1].im, c[2].im, c[3].im);
+    let four = _mm256_set1_pd(4.0);
+
+    let mut saved_re = z_re;
+    let mut saved_im = z_im;
+    let mut steps = 0u64;
+    let mut window = 8u64;
+    for i in 0..limit {
+        let re = _mm256_add_pd(
+            _mm256_sub_pd(_mm256_mul_pd(z_re, z_re), _mm256_mul_pd(z_im, z_im)),
+            c_re,
+        );
+        let im = _mm256_add_pd(
+            _mm256_add_pd(_mm256_mul_pd(z_re, z_im), _mm256_mul_pd(z_im, z_re)),
+            c_im,
+        );
+        z_re = re;
+        z_im = im;
+
+        let norm_sqr = _mm256_add_pd(_mm256_mul_pd(z_re, z_re), _mm256_mul_pd(z_im, z_im));
+        let escaped = _mm256_movemask_pd(_mm256_cmp_pd(norm_sqr, four, _CMP_GT_OQ)) & active;
+        if escaped != 0 {
+            let mut norms = [0.0; LANES];
+            _mm256_storeu_pd(norms.as_mut_ptr(), norm_sqr);
+            for lane in 0..LANES {
+                if escaped & (1 << lane) != 0 {
+                    orbits[lane] = Some((i, norms[lane]));
+                }
+            }
+            active &= !escaped;
+        }
+
+        let repeated = _mm256_movemask_pd(_mm256_and_pd(
+            _mm256_cmp_pd(z_re, saved_re, _CMP_EQ_OQ),
+            _mm256_cmp_pd(z_im, saved_im, _CMP_EQ_OQ),
+        )) & active;
+        active &= !repeated;
+        if active == 0 {
+            break;
+        }
+
+        steps += 1;
+        if steps == window {
+            saved_re = z_re;
+            saved_im = z_im;
+            steps = 0;
+            window *= 2;
+        }
+    }
+
+    orbits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(upper_left: Complex<f64>, lower_right: Complex<f64>, n: usize) -> Vec<Complex<f64>> {
+        let mut points = Vec::with_capacity(n * n);
+        for row in 0..n {
+            for column in 0..n {
+                points.push(Complex {
+                    re: upper_left.re + (lower_right.re - upper_left.re) * column as f64 / n as f64,
+                    im: upper_left.im - (upper_left.im - lower_right.im) * row as f64 / n as f64,
+                });
+            }
+        }
+        points
+    }
+
+    fn assert_matches_scalar(fractal: Fractal, points: &[Complex<f64>], limit: u32) {
+        let mut orbits = vec![None; points.len()];
+        escape_orbits(fractal, points, limit, &mut orbits);
+        for (point, orbit) in points.iter().zip(&orbits) {
+            let expected = escape_orbit(fractal, *point, limit);
+            assert_eq!(
+                orbit.map(|(count, norm_sqr)| (count, norm_sqr.to_bits())),
+                expected.map(|(count, norm_sqr)| (count, norm_sqr.to_bits())),
+                "{:?} at {}",
+                fractal,
+                point
+            );
+        }
+    }
+
+    #[test]
+    fn mandelbrot_matches_scalar_on_full_view() {
+        let points = grid(
+            Complex { re: -2.5, im: 1.5 },
+            Complex { re: 1.0, im: -1.5 },
+            97,
+        );
+        assert_matches_scalar(Fractal::Mandelbrot, &points, 500);
+    }
+
+    #[test]
+    fn mandelbrot_matches_scalar_near_boundary() {
+        let points = grid(
+            Complex {
+                re: -0.7446,
+                im: 0.1328,
+            },
+            Complex {
+                re: -0.7426,
+                im: 0.1308,
+            },
+            64,
+        );
+        assert_matches_scalar(Fractal::Mandelbrot, &points, 5000);
+    }
+
+    #[test]
+    fn julia_matches_scalar() {
+        let fractal = Fractal::Julia(Complex {
+            re: -0.8,
+            im: 0.156,
+        });
+        let points = grid(
+            Complex { re: -1.5, im: 1.0 },
+            Complex { re: 1.5, im: -1.0 },
+            61,
+        );
+        assert_matches_scalar(fractal, &points, 1000);
+    }
+
+    #[test]
+    fn unsupported_kinds_use_scalar_path() {
+        let points = grid(
+            Complex { re: -2.0, im: 2.0 },
+            Complex { re: 2.0, im: -2.0 },
+            23,
+        );
+        assert_matches_scalar(Fractal::BurningShip, &points, 200);
+        assert_matches_scalar(Fractal::Multibrot(3), &points, 200);
+    }
+
+    #[test]
+    fn escape_values_match_escape_value() {
+        let points = grid(
+            Complex { re: -2.0, im: 1.2 },
+            Complex { re: 0.6, im: -1.2 },
+            41,
+        );
+        for &coloring in &[Coloring::Integer, Coloring::Smooth] {
+            let mut escapes = vec![None; points.len()];
+            escape_values(Fractal::Mandelbrot, &points, 300, coloring, &mut escapes);
+            for (point, escape) in points.iter().zip(&escapes) {
+                let expected =
+                    escape_value(Fractal::Mandelbrot, *point, 300, coloring).map(|v| v as f32);
+                assert_eq!(escape.map(f32::to_bits), expected.map(f32::to_bits));
+            }
+        }
+    }
+}
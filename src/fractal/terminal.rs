@@ -0,0 +1,134 @@
+use super::ImageBuffer;
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorDepth {
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    pub fn from_name(name: &str) -> Option<ColorDepth> {
+        match name {
+            "256" => Some(ColorDepth::Ansi256),
+            "truecolor" | "24bit" => Some(ColorDepth::TrueColor),
+            _ => None,
+        }
+    }
+
+    pub fn detect() -> ColorDepth {
+        match std::env::var("COLORTERM") {
+            Ok(ref value) if value == "truecolor" || value == "24bit" => ColorDepth::TrueColor,
+            _ => ColorDepth::Ansi256,
+        }
+    }
+
+    fn code(self, rgb: [u8; 3]) -> String {
+        match self {
+            ColorDepth::Ansi256 => format!("5;{}", ansi256(rgb)),
+            ColorDepth::TrueColor => format!("2;{};{};{}", rgb[0], rgb[1], rgb[2]),
+        }
+    }
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(&a, &b)| (a as i32 - b as i32).pow(2) as u32)
+        .sum()
+}
+
+fn cube_index(value: u8) -> usize {
+    match value {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (value as usize - 35) / 40,
+    }
+}
+
+pub fn ansi256(rgb: [u8; 3]) -> u8 {
+    let [r, g, b] = [cube_index(rgb[0]), cube_index(rgb[1]), cube_index(rgb[2])];
+    let cube = [CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]];
+
+    let average = rgb.iter().map(|&value| value as u32).sum::<u32>() / 3;
+    let step = (average.saturating_sub(3) / 10).min(23);
+    let level = (8 + step * 10) as u8;
+    let gray = [level; 3];
+
+    if distance(rgb, gray) < distance(rgb, cube) {
+        232 + step as u8
+    } else {
+        (16 + 36 * r + 6 * g + b) as u8
+    }
+}
+
+pub fn half_blocks(image: &ImageBuffer, depth: ColorDepth) -> String {
+    let channels = image.pixels.len() / (image.width * image.height);
+    let pixel = |column: usize, row: usize| {
+        let start = (row * image.width + column) * channels;
+        let rgb = &image.pixels[start..start + 3];
+        [rgb[0], rgb[1], rgb[2]]
+    };
+
+    let mut text = String::new();
+    for row in (0..image.height).step_by(2) {
+        let mut last = None;
+        for column in 0..image.width {
+            let top = depth.code(pixel(column, row));
+            let cell = if row + 1 < image.height {
+                let bottom = depth.code(pixel(column, row + 1));
+                format!("\x1b[38;{}m\x1b[48;{}m", top, bottom)
+            } else {
+                format!("\x1b[38;{}m\x1b[49m", top)
+            };
+            if last.as_ref() != Some(&cell) {
+                text.push_str(&cell);
+                last = Some(cell);
+            }
+            text.push('\u{2580}');
+        }
+        text.push_str("\x1b[0m\r\n");
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ColorType;
+
+    #[test]
+    fn ansi256_picks_cube_and_gray_entries() {
+        assert_eq!(ansi256([0, 0, 0]), 16);
+        assert_eq!(ansi256([255, 255, 255]), 231);
+        assert_eq!(ansi256([255, 0, 0]), 196);
+        assert_eq!(ansi256([0, 135, 255]), 33);
+        assert_eq!(ansi256([128, 128, 128]), 244);
+    }
+
+    #[test]
+    fn half_blocks_pair_rows_into_cells() {
+        let image = ImageBuffer {
+            width: 2,
+            height: 3,
+            color_type: ColorType::RGB(8),
+            pixels: vec![
+                255, 0, 0, 255, 0, 0, //
+                0, 0, 255, 0, 0, 255, //
+                0, 255, 0, 9, 9, 9,
+            ],
+        };
+        let text = half_blocks(&image, ColorDepth::TrueColor);
+        let lines: Vec<&str> = text.split("\r\n").collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m\u{2580}\u{2580}\x1b[0m"
+        );
+        assert_eq!(
+            lines[1],
+            "\x1b[38;2;0;255;0m\x1b[49m\u{2580}\x1b[38;2;9;9;9m\x1b[49m\u{2580}\x1b[0m"
+        );
+    }
+}
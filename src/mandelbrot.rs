@@ -1,132 +1,1849 @@
+use image::ColorType;
 use num::Complex;
+use rust_sandbox::fractal::palette::Palette;
+use rust_sandbox::fractal::{
+    colorize, density_pixels, density_values, escape_time, frame_corners, half_blocks,
+    parse_center, parse_complex, parse_pair, plain_escape_time, read_dump, read_metadata,
+    read_scenes, render_density, render_view, shaded_for, simd, write_dump, write_escapes,
+    write_image, write_metadata, Center, ColorDepth, Coloring, Density, DoubleDouble, Dump, Format,
+    Fractal, Options, Precision, Real, Region, RenderRequest, Rotation, Sampling, TileScheduler,
+    View,
+};
+
+fn frame_filename(filename: &str, frame: usize) -> String {
+    match filename.rfind('.') {
+        Some(index) if !filename[index..].contains('/') => {
+            format!("{}-{:05}{}", &filename[..index], frame, &filename[index..])
+        }
+        _ => format!("{}-{:05}", filename, frame),
+    }
+}
+
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+fn tile_manifest_header(config: &cli::Config, tile_size: usize) -> String {
+    let (upper_left, lower_right) = config.view.corners(config.bounds);
+    format!(
+        "pixels {}x{}\nupper-left {},{}\nlower-right {},{}\nrotation {:?}\ntile {}\nfractal {:?}\niterations {}\ncoloring {:?}\nsampling {:?}\npalette {}\n",
+        config.bounds.0,
+        config.bounds.1,
+        upper_left.re,
+        upper_left.im,
+        lower_right.re,
+        lower_right.im,
+        config.view.rotation(config.bounds),
+        tile_size,
+        config.fractal,
+        config.options.limit,
+        config.options.coloring,
+        config.options.sampling,
+        config.options.palette
+    )
+}
+
+fn read_manifest(path: &Path, header: &str) -> Result<HashSet<(usize, usize)>, std::io::Error> {
+    let text = std::fs::read_to_string(path)?;
+    // A run killed mid-write leaves an unterminated last line; it is dropped
+    // and that tile is simply rendered again.
+    let complete = text.rfind('\n').map_or("", |end| &text[..=end]);
+
+    let mut existing = String::new();
+    let mut done = HashSet::new();
+    for line in complete.lines() {
+        match line.strip_prefix("done ") {
+            Some(tile) => match parse_pair(tile, ' ') {
+                Some(tile) => {
+                    done.insert(tile);
+                }
+                None => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("{} has a corrupt entry `{}`", path.display(), line),
+                    ))
+                }
+            },
+            None => {
+                existing.push_str(line);
+                existing.push('\n');
+            }
+        }
+    }
+
+    if existing != header {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "{} belongs to a different render; use another directory",
+                path.display()
+            ),
+        ));
+    }
+    if complete.len() < text.len() {
+        std::fs::write(path, complete)?;
+    }
 
-fn escape_time(c: Complex<f64>, limit: u32) -> Option<u32> {
-    let mut z = Complex { re: 0.0, im: 0.0 };
-    for i in 0..limit {
-        z = z * z + c;
-        if z.norm_sqr() > 4.0 {
-            return Some(i);
+    Ok(done)
+}
+
+fn render_tiles(
+    config: &cli::Config,
+    tile_size: usize,
+    palette: &Palette,
+    scheduler: &TileScheduler,
+) -> Result<(), std::io::Error> {
+    let dir = &config.output;
+    let bounds = config.bounds;
+    let view = config.view.at(bounds);
+    std::fs::create_dir_all(dir)?;
+
+    let manifest_path = Path::new(dir).join("manifest.txt");
+    let header = tile_manifest_header(config, tile_size);
+    let done = if manifest_path.exists() {
+        read_manifest(&manifest_path, &header)?
+    } else {
+        std::fs::write(&manifest_path, &header)?;
+        HashSet::new()
+    };
+    let mut manifest = OpenOptions::new().append(true).open(&manifest_path)?;
+
+    let rows = bounds.1.div_ceil(tile_size);
+    let columns = bounds.0.div_ceil(tile_size);
+    for row in 0..rows {
+        for column in 0..columns {
+            if done.contains(&(row, column)) {
+                continue;
+            }
+
+            let (left, top) = (column * tile_size, row * tile_size);
+            let region = Region {
+                left,
+                top,
+                width: tile_size.min(bounds.0 - left),
+                height: tile_size.min(bounds.1 - top),
+            };
+            let tile_bounds = (region.width, region.height);
+
+            let samples = config.options.sampling.samples();
+            let mut escapes = vec![None; region.pixels() * samples];
+            render_view(
+                &mut escapes,
+                view,
+                region,
+                config.fractal,
+                &config.options,
+                scheduler,
+            );
+
+            let mut pixels = vec![0; tile_bounds.0 * tile_bounds.1 * palette.channels()];
+            let shaded = shaded_for(&escapes, palette, &config.options);
+            colorize(&mut pixels, &shaded, palette, config.options.fixed_range());
+
+            let filename = Path::new(dir).join(format!("tile-{:04}-{:04}.png", row, column));
+            write_image(
+                &filename.to_string_lossy(),
+                &pixels,
+                tile_bounds,
+                palette.color_type(),
+            )?;
+
+            writeln!(manifest, "done {} {}", row, column)?;
+            manifest.sync_data()?;
         }
     }
 
-    None
+    Ok(())
 }
 
-use std::str::FromStr;
+mod cli {
+    use super::{
+        parse_center, parse_complex, parse_pair, ColorDepth, Coloring, Density, DoubleDouble,
+        Format, Fractal, Options, Precision, Real, Rotation, Sampling,
+    };
+    use num::Complex;
+    use std::fmt;
+    use std::str::FromStr;
+
+    pub const USAGE: &str = "\
+Usage: mandelbrot [COMMAND] [KIND] --output FILE [OPTIONS]
+
+Commands:
+    render                  render a single image (default)
+    deep                    render a deep zoom with perturbation; needs --center and --zoom or --width
+    sequence                render numbered zoom frames from --zoom to --end-zoom
+    tiles                   render into tiles in the --output directory, resuming if interrupted
+    bench                   time the plain, interior-checked and SIMD escape-time kernels
+    recolor                 color a saved iteration dump given with --input without re-rendering
+    rerender                re-render the png given with --input from its embedded parameters;
+                            --size, --center, --zoom (relative), -i, -p and --smooth override them
+    batch                   render every scene in the TOML or JSON file given with --input,
+                            skipping up-to-date outputs, and write a report to --output;
+                            TOML scene files are limited to `#` comments, [[scene]] tables
+                            and one-line `key = value` entries with double-quoted strings,
+                            numbers, booleans or one-line arrays
+    buddhabrot              plot the density of escaping orbits from random samples
+    explore                 pan and zoom in the terminal; arrows or hjkl pan, +/- zoom,
+                            [/] change iterations, q or Esc prints the view and quits
+
+Kinds:
+    mandelbrot (default), julia RE,IM, multibrot DEGREE, burning-ship, tricorn
+
+Options:
+    -o, --output FILE       output file (directory for tiles, report for batch)
+    -s, --size WxH          image size in pixels [default: 800x600]
+        --upper-left RE,IM  upper left corner of the view
+        --lower-right RE,IM lower right corner of the view
+    -c, --center RE,IM      center of the view [default: -0.75,0]
+    -z, --zoom Z            magnification; zoom 1 is 4 units wide [default: 1]
+    -w, --width W           width of the view; the height follows the image aspect ratio
+    -r, --rotate DEGREES    rotate the view counterclockwise about its center [default: 0]
+    -i, --iterations N      iteration limit [default: 255]
+    -p, --palette NAME      gray, fire, ocean, rainbow, ultra, or a palette file [default: gray]
+                            palette files list one RRGGBB or RRGGBBAA color per line
+                            (`#` optional), with `;` comments, `cycle N` to repeat the
+                            colors every N iterations and `interior COLOR` for the set
+        --smooth            use smooth (continuous) iteration coloring
+        --distance          shade by estimated distance to the set (line art)
+        --equalize          spread colors by the histogram of escape values
+    -a, --supersample MODE  grid:N, jitter:N or adaptive:N samples per axis [default: none]
+        --no-simd           use the scalar escape-time kernel only
+        --precision TYPE    render with f32 (fast preview), f64 or dd (double-double, deeper
+                            zooms) arithmetic [default: f64]
+        --fill              fill rectangles whose border shares one value (Mariani-Silver)
+    -t, --threads N         number of render threads [default: one per CPU]
+        --stats             print per-thread timing statistics to stderr
+        --save-escapes FILE also save integer and smooth escape values for recolor
+        --input FILE        iteration dump to recolor, png to rerender, or scene file to batch
+    -f, --format FORMAT     png, png16, ppm, pgm, tga, raw (u32 counts) or dump (recolorable)
+                            [default: from the output extension, else png]
+        --end-zoom Z        final zoom of a sequence
+        --frames N          number of frames in a sequence
+        --tile-size N       tile edge length in pixels [default: 1024]
+        --samples N         random orbits traced by buddhabrot [default: 1000000]
+        --seed N            random seed for buddhabrot samples [default: 0]
+        --nebula R,G,B      per-channel iteration limits for a buddhabrot in color (png only)
+        --color DEPTH       explore with 256 or truecolor ANSI colors [default: from $COLORTERM]
+    -h, --help              print this message
 
-fn parse_pair<T: FromStr>(s: &str, separator: char) -> Option<(T, T)> {
-    match s.find(separator) {
-        None => None,
-        Some(index) => match (T::from_str(&s[..index]), T::from_str(&s[index + 1..])) {
-            (Ok(l), Ok(r)) => Some((l, r)),
-            _ => None,
+Example:
+    mandelbrot julia -0.8,0.156 -o julia.png -s 1000x750 -z 1.5 -p ultra --smooth";
+
+    pub const DEFAULT_WIDTH: f64 = 4.0;
+
+    #[derive(Debug, PartialEq)]
+    pub enum CliError {
+        UnexpectedArgument(String),
+        MissingValue(String),
+        InvalidValue {
+            flag: String,
+            value: String,
+            expected: &'static str,
+        },
+        MissingFlag(&'static str),
+        Conflict(&'static str, &'static str),
+        Unsupported {
+            command: &'static str,
+            reason: &'static str,
+        },
+    }
+
+    impl fmt::Display for CliError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                CliError::UnexpectedArgument(arg) => write!(f, "unexpected argument `{}`", arg),
+                CliError::MissingValue(flag) => write!(f, "`{}` needs a value", flag),
+                CliError::InvalidValue {
+                    flag,
+                    value,
+                    expected,
+                } => write!(
+                    f,
+                    "invalid value `{}` for `{}`: expected {}",
+                    value, flag, expected
+                ),
+                CliError::MissingFlag(flag) => write!(f, "missing required `{}`", flag),
+                CliError::Conflict(a, b) => write!(f, "`{}` cannot be used with `{}`", a, b),
+                CliError::Unsupported { command, reason } => write!(f, "{}: {}", command, reason),
+            }
+        }
+    }
+
+    impl std::error::Error for CliError {}
+
+    #[derive(Clone, PartialEq, Debug)]
+    pub enum Frame {
+        Corners(Complex<f64>, Complex<f64>),
+        Center {
+            center: Complex<f64>,
+            text: String,
+            width: f64,
         },
     }
+
+    #[derive(Clone, PartialEq, Debug)]
+    pub struct View {
+        pub frame: Frame,
+        pub degrees: f64,
+    }
+
+    impl View {
+        pub fn corners(&self, bounds: (usize, usize)) -> (Complex<f64>, Complex<f64>) {
+            match &self.frame {
+                Frame::Corners(upper_left, lower_right) => (*upper_left, *lower_right),
+                Frame::Center { center, width, .. } => {
+                    super::frame_corners(*center, *width, bounds)
+                }
+            }
+        }
+
+        pub fn rotation(&self, bounds: (usize, usize)) -> Rotation {
+            let (upper_left, lower_right) = self.corners(bounds);
+            Rotation::new((upper_left + lower_right) / 2.0, self.degrees)
+        }
+
+        pub fn at(&self, bounds: (usize, usize)) -> super::View {
+            let (upper_left, lower_right) = self.corners(bounds);
+            super::View {
+                bounds,
+                upper_left,
+                lower_right,
+                rotation: self.rotation(bounds),
+            }
+        }
+    }
+
+    #[derive(Clone, PartialEq, Debug)]
+    pub enum Command {
+        Render,
+        Deep,
+        Sequence { end_zoom: f64, frames: usize },
+        Tiles { tile_size: usize },
+        Bench,
+        Recolor { input: String },
+        Batch { input: String },
+        Rerender { input: String, overrides: Overrides },
+        Buddhabrot { density: Density },
+        Explore { depth: ColorDepth },
+    }
+
+    #[derive(Clone, PartialEq, Debug)]
+    pub struct Overrides {
+        pub size: Option<(usize, usize)>,
+        pub center: Option<(Complex<f64>, String)>,
+        pub zoom: Option<f64>,
+        pub limit: Option<u32>,
+        pub palette: Option<String>,
+        pub coloring: Option<Coloring>,
+    }
+
+    pub struct Config {
+        pub command: Command,
+        pub fractal: Fractal,
+        pub output: String,
+        pub bounds: (usize, usize),
+        pub view: View,
+        pub threads: Option<usize>,
+        pub stats: bool,
+        pub save_escapes: Option<String>,
+        pub format: Format,
+        pub precision: Precision,
+        pub options: Options,
+    }
+
+    fn invalid(flag: &str, value: &str, expected: &'static str) -> CliError {
+        CliError::InvalidValue {
+            flag: flag.to_string(),
+            value: value.to_string(),
+            expected,
+        }
+    }
+
+    fn parse_positive<T: FromStr + PartialOrd + Default>(
+        flag: &str,
+        value: &str,
+        expected: &'static str,
+    ) -> Result<T, CliError> {
+        match value.parse() {
+            Ok(parsed) if parsed > T::default() => Ok(parsed),
+            _ => Err(invalid(flag, value, expected)),
+        }
+    }
+
+    fn parse_scale(flag: &str, value: &str) -> Result<f64, CliError> {
+        parse_positive(flag, value, "a positive number")
+            .ok()
+            .filter(|scale: &f64| scale.is_finite())
+            .ok_or_else(|| invalid(flag, value, "a positive number"))
+    }
+
+    fn parse_point(flag: &str, value: &str) -> Result<Complex<f64>, CliError> {
+        parse_complex(value)
+            .filter(|point: &Complex<f64>| point.re.is_finite() && point.im.is_finite())
+            .ok_or_else(|| invalid(flag, value, "a point like -1.20,0.35"))
+    }
+
+    fn parses_as<T: Real>(text: &str) -> bool {
+        parse_complex::<T>(text)
+            .is_some_and(|point| point.re.to_f64().is_finite() && point.im.to_f64().is_finite())
+    }
+
+    fn parse_sampling(flag: &str, value: &str) -> Result<Sampling, CliError> {
+        let expected = "none, grid:N, jitter:N or adaptive:N";
+        let (mode, n) = match value.find(':') {
+            Some(index) => match value[index + 1..].parse() {
+                Ok(n) if (1..=16).contains(&n) => (&value[..index], n),
+                _ => return Err(invalid(flag, value, expected)),
+            },
+            None => (value, 3),
+        };
+
+        match mode {
+            "none" => Ok(Sampling::Single),
+            "grid" => Ok(Sampling::Grid(n)),
+            "jitter" => Ok(Sampling::Jitter(n)),
+            "adaptive" => Ok(Sampling::Adaptive(n)),
+            _ => Err(invalid(flag, value, expected)),
+        }
+    }
+
+    fn parse_limits(flag: &str, value: &str) -> Result<Vec<u32>, CliError> {
+        let limits: Vec<u32> = value
+            .split(',')
+            .filter_map(|limit| limit.parse().ok())
+            .filter(|&limit| limit > 0)
+            .collect();
+        if limits.len() != 3 || value.split(',').count() != 3 {
+            return Err(invalid(
+                flag,
+                value,
+                "three iteration limits like 5000,500,50",
+            ));
+        }
+        Ok(limits)
+    }
+
+    fn parse_kind(args: &[String]) -> Result<(Fractal, usize), CliError> {
+        let parameter = |kind: &str| {
+            args.get(1)
+                .filter(|arg| !arg.starts_with("--"))
+                .ok_or_else(|| CliError::MissingValue(kind.to_string()))
+        };
+
+        match args.first().map(String::as_str) {
+            Some("mandelbrot") => Ok((Fractal::Mandelbrot, 1)),
+            Some("julia") => {
+                let c = parameter("julia")?;
+                Ok((Fractal::Julia(parse_point("julia", c)?), 2))
+            }
+            Some("multibrot") => {
+                let degree = parameter("multibrot")?;
+                match degree.parse() {
+                    Ok(degree) if degree >= 2 => Ok((Fractal::Multibrot(degree), 2)),
+                    _ => Err(invalid(
+                        "multibrot",
+                        degree,
+                        "an integer degree of at least 2",
+                    )),
+                }
+            }
+            Some("burning-ship") => Ok((Fractal::BurningShip, 1)),
+            Some("tricorn") => Ok((Fractal::Tricorn, 1)),
+            _ => Ok((Fractal::Mandelbrot, 0)),
+        }
+    }
+
+    pub fn parse_args(args: &[String]) -> Result<Config, CliError> {
+        let (command, args) = match args.first().map(String::as_str) {
+            Some("render") => ("render", &args[1..]),
+            Some("deep") => ("deep", &args[1..]),
+            Some("sequence") => ("sequence", &args[1..]),
+            Some("tiles") => ("tiles", &args[1..]),
+            Some("bench") => ("bench", &args[1..]),
+            Some("recolor") => ("recolor", &args[1..]),
+            Some("batch") => ("batch", &args[1..]),
+            Some("rerender") => ("rerender", &args[1..]),
+            Some("buddhabrot") => ("buddhabrot", &args[1..]),
+            Some("explore") => ("explore", &args[1..]),
+            _ => ("render", args),
+        };
+        let (fractal, consumed) = parse_kind(args)?;
+
+        let mut output = None;
+        let mut bounds = (800, 600);
+        let mut size = None;
+        let mut limit = None;
+        let mut palette = None;
+        let mut upper_left = None;
+        let mut lower_right = None;
+        let mut center = None;
+        let mut zoom = None;
+        let mut width = None;
+        let mut degrees = 0.0;
+        let mut threads = None;
+        let mut stats = false;
+        let mut smooth = false;
+        let mut distance = false;
+        let mut save_escapes = None;
+        let mut input = None;
+        let mut format = None;
+        let mut end_zoom = None;
+        let mut frames = None;
+        let mut tile_size = None;
+        let mut samples = None;
+        let mut seed = None;
+        let mut nebula = None;
+        let mut depth = None;
+        let mut precision = None;
+        let mut options = Options::default();
+
+        let mut args = args[consumed..].iter();
+        while let Some(flag) = args.next() {
+            let flag = flag.as_str();
+            if flag == "--smooth" {
+                smooth = true;
+                options.coloring = Coloring::Smooth;
+                continue;
+            }
+            if flag == "--distance" {
+                distance = true;
+                options.coloring = Coloring::Distance;
+                continue;
+            }
+            if flag == "--stats" {
+                stats = true;
+                continue;
+            }
+            if flag == "--equalize" {
+                options.equalize = true;
+                continue;
+            }
+            if flag == "--fill" {
+                options.fill = true;
+                continue;
+            }
+            if flag == "--no-simd" {
+                options.vectorize = false;
+                continue;
+            }
+            if !flag.starts_with('-') {
+                return Err(CliError::UnexpectedArgument(flag.to_string()));
+            }
+
+            let value = args
+                .next()
+                .ok_or_else(|| CliError::MissingValue(flag.to_string()))?;
+            match flag {
+                "-o" | "--output" => output = Some(value.clone()),
+                "-s" | "--size" => {
+                    bounds = match parse_pair(value, 'x') {
+                        Some((width, height)) if width > 0 && height > 0 => (width, height),
+                        _ => return Err(invalid(flag, value, "a size like 1000x750")),
+                    };
+                    size = Some(bounds);
+                }
+                "--upper-left" => upper_left = Some(parse_point(flag, value)?),
+                "--lower-right" => lower_right = Some(parse_point(flag, value)?),
+                "-c" | "--center" => center = Some((parse_point(flag, value)?, value.clone())),
+                "-z" | "--zoom" => zoom = Some(parse_scale(flag, value)?),
+                "-w" | "--width" => width = Some(parse_scale(flag, value)?),
+                "-r" | "--rotate" => {
+                    degrees = match value.parse::<f64>() {
+                        Ok(degrees) if degrees.is_finite() => degrees,
+                        _ => return Err(invalid(flag, value, "an angle in degrees")),
+                    }
+                }
+                "-i" | "--iterations" => {
+                    options.limit = parse_positive(flag, value, "a positive integer")?;
+                    limit = Some(options.limit);
+                }
+                "-p" | "--palette" => {
+                    options.palette = value.clone();
+                    palette = Some(value.clone());
+                }
+                "-a" | "--supersample" => options.sampling = parse_sampling(flag, value)?,
+                "-t" | "--threads" => {
+                    threads = Some(parse_positive(flag, value, "a positive integer")?)
+                }
+                "--save-escapes" => save_escapes = Some(value.clone()),
+                "--input" => input = Some(value.clone()),
+                "-f" | "--format" => {
+                    format = Some(Format::from_name(value).ok_or_else(|| {
+                        invalid(flag, value, "png, png16, ppm, pgm, tga, raw or dump")
+                    })?)
+                }
+                "--end-zoom" => end_zoom = Some(parse_scale(flag, value)?),
+                "--frames" => frames = Some(parse_positive(flag, value, "a positive integer")?),
+                "--tile-size" => {
+                    tile_size = Some(parse_positive(flag, value, "a positive integer")?)
+                }
+                "--samples" => samples = Some(parse_positive(flag, value, "a positive integer")?),
+                "--seed" => {
+                    seed = Some(
+                        value
+                            .parse()
+                            .map_err(|_| invalid(flag, value, "a non-negative integer"))?,
+                    )
+                }
+                "--nebula" => nebula = Some(parse_limits(flag, value)?),
+                "--color" => {
+                    depth = Some(
+                        ColorDepth::from_name(value)
+                            .ok_or_else(|| invalid(flag, value, "256 or truecolor"))?,
+                    )
+                }
+                "--precision" => {
+                    precision = Some(
+                        Precision::from_name(value)
+                            .filter(|&precision| precision != Precision::Deep)
+                            .ok_or_else(|| invalid(flag, value, "f32, f64 or dd"))?,
+                    )
+                }
+                _ => return Err(CliError::UnexpectedArgument(flag.to_string())),
+            }
+        }
+
+        let output = match output {
+            Some(output) => output,
+            None if command == "bench" || command == "explore" => String::new(),
+            None => return Err(CliError::MissingFlag("--output")),
+        };
+
+        if zoom.is_some() && width.is_some() {
+            return Err(CliError::Conflict("--zoom", "--width"));
+        }
+        if options.fill && options.sampling.samples() > 1 {
+            return Err(CliError::Conflict("--fill", "--supersample"));
+        }
+        if smooth && distance {
+            return Err(CliError::Conflict("--smooth", "--distance"));
+        }
+
+        let overrides = Overrides {
+            size,
+            center: center.clone(),
+            zoom,
+            limit,
+            palette,
+            coloring: if smooth || distance {
+                Some(options.coloring)
+            } else {
+                None
+            },
+        };
+        if command == "rerender" {
+            if consumed > 0 {
+                return Err(CliError::Unsupported {
+                    command,
+                    reason: "the fractal kind comes from the image",
+                });
+            }
+            let moved = upper_left.is_some() || lower_right.is_some() || width.is_some();
+            if moved || degrees != 0.0 {
+                return Err(CliError::Unsupported {
+                    command,
+                    reason: "the view comes from the image; move it with `--center` and `--zoom`",
+                });
+            }
+        }
+
+        let frame = match (upper_left, lower_right, center) {
+            (Some(_), _, Some(_)) => return Err(CliError::Conflict("--upper-left", "--center")),
+            (_, Some(_), Some(_)) => return Err(CliError::Conflict("--lower-right", "--center")),
+            (Some(_), None, None) => return Err(CliError::MissingFlag("--lower-right")),
+            (None, Some(_), None) => return Err(CliError::MissingFlag("--upper-left")),
+            (Some(upper_left), Some(lower_right), None) => {
+                if zoom.is_some() {
+                    return Err(CliError::Conflict("--zoom", "--upper-left"));
+                }
+                if width.is_some() {
+                    return Err(CliError::Conflict("--width", "--upper-left"));
+                }
+                Frame::Corners(upper_left, lower_right)
+            }
+            (None, None, center) => {
+                let (center, text) =
+                    center.unwrap_or((Complex { re: -0.75, im: 0.0 }, "-0.75,0".to_string()));
+                Frame::Center {
+                    center,
+                    text,
+                    width: width.unwrap_or(DEFAULT_WIDTH / zoom.unwrap_or(1.0)),
+                }
+            }
+        };
+        let view = View { frame, degrees };
+
+        if command != "sequence" {
+            if end_zoom.is_some() {
+                return Err(CliError::Unsupported {
+                    command,
+                    reason: "`--end-zoom` only applies to sequence",
+                });
+            }
+            if frames.is_some() {
+                return Err(CliError::Unsupported {
+                    command,
+                    reason: "`--frames` only applies to sequence",
+                });
+            }
+        }
+        if command == "tiles" && format.is_some() && format != Some(Format::Png) {
+            return Err(CliError::Unsupported {
+                command,
+                reason: "tiles are always written as png",
+            });
+        }
+        let format = format
+            .or_else(|| Format::from_extension(&output))
+            .unwrap_or(Format::Png);
+        if format == Format::Raw && options.coloring != Coloring::Integer {
+            return Err(CliError::Unsupported {
+                command,
+                reason: "raw files hold integer escape counts; drop `--smooth` and `--distance`",
+            });
+        }
+        if command != "render" && save_escapes.is_some() {
+            return Err(CliError::Unsupported {
+                command,
+                reason: "`--save-escapes` only applies to render",
+            });
+        }
+        if !["recolor", "batch", "rerender"].contains(&command) && input.is_some() {
+            return Err(CliError::Unsupported {
+                command,
+                reason: "`--input` only applies to recolor, rerender and batch",
+            });
+        }
+        if command == "tiles" && options.equalize {
+            return Err(CliError::Unsupported {
+                command,
+                reason: "tiles are colored independently and cannot be equalized",
+            });
+        }
+        if distance && command == "deep" {
+            return Err(CliError::Unsupported {
+                command,
+                reason: "`--distance` needs the full-precision derivative",
+            });
+        }
+        if distance && !fractal.supports_distance() {
+            return Err(CliError::Unsupported {
+                command,
+                reason: "`--distance` only applies to mandelbrot, julia and multibrot",
+            });
+        }
+        if command != "buddhabrot" {
+            let reason = match (samples, seed, &nebula) {
+                (Some(_), _, _) => Some("`--samples` only applies to buddhabrot"),
+                (_, Some(_), _) => Some("`--seed` only applies to buddhabrot"),
+                (_, _, Some(_)) => Some("`--nebula` only applies to buddhabrot"),
+                _ => None,
+            };
+            if let Some(reason) = reason {
+                return Err(CliError::Unsupported { command, reason });
+            }
+        }
+        if command != "explore" && depth.is_some() {
+            return Err(CliError::Unsupported {
+                command,
+                reason: "`--color` only applies to explore",
+            });
+        }
+        if command != "render" && precision.is_some() {
+            return Err(CliError::Unsupported {
+                command,
+                reason: "`--precision` only applies to render",
+            });
+        }
+        let precision = precision.unwrap_or(Precision::Double);
+        if let Frame::Center { text, .. } = &view.frame {
+            let parses = match precision {
+                Precision::Single => parses_as::<f32>(text),
+                Precision::Double => command != "deep" || parse_center(text, 64).is_some(),
+                Precision::Deep => parse_center(text, 64).is_some(),
+                Precision::DoubleDouble => parses_as::<DoubleDouble>(text),
+            };
+            if !parses {
+                return Err(invalid("--center", text, "a point like -1.20,0.35"));
+            }
+        }
+        if precision != Precision::Double {
+            if options.fill {
+                return Err(CliError::Conflict("--precision", "--fill"));
+            }
+            if let Sampling::Adaptive(_) = options.sampling {
+                return Err(CliError::Conflict("--precision", "--supersample"));
+            }
+            if save_escapes.is_some() {
+                return Err(CliError::Conflict("--precision", "--save-escapes"));
+            }
+        }
+        if distance && precision == Precision::DoubleDouble {
+            return Err(CliError::Unsupported {
+                command,
+                reason: "the distance estimate runs in f64; drop `--distance` or `--precision dd`",
+            });
+        }
+        if command != "tiles" && tile_size.is_some() {
+            return Err(CliError::Unsupported {
+                command,
+                reason: "`--tile-size` only applies to tiles",
+            });
+        }
+
+        let command = match command {
+            "deep" => {
+                if fractal != Fractal::Mandelbrot {
+                    return Err(CliError::Unsupported {
+                        command,
+                        reason: "perturbation only supports the mandelbrot kind",
+                    });
+                }
+                if let Frame::Corners(..) = view.frame {
+                    return Err(CliError::Unsupported {
+                        command,
+                        reason: "the view must be given with `--center` and `--zoom` or `--width`",
+                    });
+                }
+                Command::Deep
+            }
+            "sequence" => {
+                if let Frame::Corners(..) = view.frame {
+                    return Err(CliError::Unsupported {
+                        command,
+                        reason: "the view must be given with `--center` and `--zoom` or `--width`",
+                    });
+                }
+                Command::Sequence {
+                    end_zoom: end_zoom.ok_or(CliError::MissingFlag("--end-zoom"))?,
+                    frames: frames.ok_or(CliError::MissingFlag("--frames"))?,
+                }
+            }
+            "tiles" => Command::Tiles {
+                tile_size: tile_size.unwrap_or(1024),
+            },
+            "bench" => Command::Bench,
+            "buddhabrot" => {
+                if options.coloring != Coloring::Integer {
+                    return Err(CliError::Unsupported {
+                        command,
+                        reason: "orbit densities are not escape values; drop `--smooth` and `--distance`",
+                    });
+                }
+                if options.fill || options.sampling.samples() > 1 {
+                    return Err(CliError::Unsupported {
+                        command,
+                        reason: "orbit densities cannot be filled or supersampled",
+                    });
+                }
+                if nebula.is_some() && format != Format::Png {
+                    return Err(CliError::Unsupported {
+                        command,
+                        reason: "`--nebula` images are always written as png",
+                    });
+                }
+                Command::Buddhabrot {
+                    density: Density {
+                        samples: samples.unwrap_or(1_000_000),
+                        seed: seed.unwrap_or(0),
+                        limits: nebula.unwrap_or_else(|| vec![options.limit]),
+                    },
+                }
+            }
+            "explore" => Command::Explore {
+                depth: depth.unwrap_or_else(ColorDepth::detect),
+            },
+            "rerender" => Command::Rerender {
+                input: input.ok_or(CliError::MissingFlag("--input"))?,
+                overrides,
+            },
+            "batch" => Command::Batch {
+                input: input.ok_or(CliError::MissingFlag("--input"))?,
+            },
+            "recolor" => Command::Recolor {
+                input: input.ok_or(CliError::MissingFlag("--input"))?,
+            },
+            _ => Command::Render,
+        };
+
+        Ok(Config {
+            command,
+            fractal,
+            output,
+            bounds,
+            view,
+            threads,
+            stats,
+            save_escapes,
+            format,
+            precision,
+            options,
+        })
+    }
 }
 
-fn parse_complex(s: &str) -> Option<Complex<f64>> {
-    match parse_pair(s, ',') {
-        Some((re, im)) => Some(Complex { re, im }),
-        None => None,
+use std::time::{Duration, Instant};
+
+const BENCHMARK_VIEWS: [(&str, f64, f64, f64); 4] = [
+    ("full set", -0.75, 0.0, 3.5),
+    ("main cardioid", -0.1, 0.0, 1.0),
+    ("seahorse valley", -0.7436, 0.1318, 0.01),
+    ("elephant valley", 0.2925, 0.0148, 0.02),
+];
+
+fn time_kernel<F>(view: View, kernel: F) -> (Duration, Vec<Option<u32>>)
+where
+    F: Fn(Complex<f64>) -> Option<u32>,
+{
+    let start = Instant::now();
+    let mut counts = Vec::with_capacity(view.bounds.0 * view.bounds.1);
+    for row in 0..view.bounds.1 {
+        for column in 0..view.bounds.0 {
+            let point = view.pixel_to_point((column, row));
+            counts.push(kernel(point));
+        }
     }
+    (start.elapsed(), counts)
 }
 
-fn pixel_to_point(
-    bounds: (usize, usize),
-    pixel: (usize, usize),
-    upper_left: Complex<f64>,
-    lower_right: Complex<f64>,
-) -> Complex<f64> {
-    let (width, height) = (
-        lower_right.re - upper_left.re,
-        upper_left.im - lower_right.im,
+fn run_benchmark(config: &cli::Config) {
+    let limit = config.options.limit;
+    println!(
+        "{}x{} pixels, {} iterations, one thread",
+        config.bounds.0, config.bounds.1, limit
+    );
+    println!(
+        "{:<18} {:>10} {:>10} {:>10} {:>8} {:>10}",
+        "view", "plain ms", "checked ms", "simd ms", "speedup", "mismatch"
+    );
+
+    for &(name, re, im, width) in BENCHMARK_VIEWS.iter() {
+        let (upper_left, lower_right) = frame_corners(Complex { re, im }, width, config.bounds);
+        let view = View {
+            bounds: config.bounds,
+            upper_left,
+            lower_right,
+            rotation: Rotation::none(),
+        };
+        let (plain, expected) = time_kernel(view, |point| {
+            plain_escape_time(config.fractal, point, limit)
+        });
+        let (checked, actual) =
+            time_kernel(view, |point| escape_time(config.fractal, point, limit));
+        let start = Instant::now();
+        let mut vectorized = Vec::with_capacity(actual.len());
+        let mut points = Vec::with_capacity(config.bounds.0);
+        let mut orbits = vec![None; config.bounds.0];
+        for row in 0..config.bounds.1 {
+            points.clear();
+            for column in 0..config.bounds.0 {
+                points.push(view.pixel_to_point((column, row)));
+            }
+            simd::escape_orbits(config.fractal, &points, limit, &mut orbits);
+            vectorized.extend(orbits.iter().map(|orbit| orbit.map(|(count, _)| count)));
+        }
+        let simd = start.elapsed();
+
+        let mismatched = expected
+            .iter()
+            .zip(&actual)
+            .zip(&vectorized)
+            .filter(|((a, b), c)| a != b || b != c)
+            .count();
+
+        println!(
+            "{:<18} {:>10.1} {:>10.1} {:>10.1} {:>7.2}x {:>10}",
+            name,
+            plain.as_secs_f64() * 1000.0,
+            checked.as_secs_f64() * 1000.0,
+            simd.as_secs_f64() * 1000.0,
+            plain.as_secs_f64() / simd.as_secs_f64().max(1e-9),
+            mismatched
+        );
+    }
+}
+
+fn up_to_date(output: &str, inputs: &[&str]) -> bool {
+    let modified = |path: &str| {
+        std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok()
+    };
+    match modified(output) {
+        Some(rendered) => inputs
+            .iter()
+            .all(|input| modified(input).is_none_or(|changed| changed <= rendered)),
+        None => false,
+    }
+}
+
+fn run_batch(
+    config: &cli::Config,
+    input: &str,
+    pool: &rayon::ThreadPool,
+    scheduler: &TileScheduler,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let scenes = read_scenes(input)?;
+
+    let mut report = String::new();
+    let (mut rendered, mut skipped, mut failed) = (0, 0, 0);
+    for scene in &scenes {
+        let request = &scene.request;
+        if up_to_date(&scene.output, &[input, &request.options.palette]) {
+            report.push_str(&format!("up to date {}\n", scene.output));
+            skipped += 1;
+            continue;
+        }
+
+        let start = Instant::now();
+        let result = Palette::from_spec(&request.options.palette).and_then(|palette| {
+            let escapes = pool.install(|| request.escapes_with(scheduler))?;
+            write_escapes(
+                &scene.output,
+                &escapes,
+                request.bounds,
+                &palette,
+                scene.format,
+                &request.options,
+                None,
+            )?;
+            write_metadata(&scene.output, scene.format, request)
+        });
+        match result {
+            Ok(()) => {
+                report.push_str(&format!(
+                    "rendered {} {}x{} in {:.3}s\n",
+                    scene.output,
+                    request.bounds.0,
+                    request.bounds.1,
+                    start.elapsed().as_secs_f64()
+                ));
+                rendered += 1;
+            }
+            Err(error) => {
+                report.push_str(&format!("failed {}: {}\n", scene.output, error));
+                failed += 1;
+            }
+        }
+    }
+
+    let summary = format!(
+        "{} rendered, {} up to date, {} failed",
+        rendered, skipped, failed
     );
-    Complex {
-        re: upper_left.re + pixel.0 as f64 / bounds.0 as f64 * width,
-        im: upper_left.im - pixel.1 as f64 / bounds.1 as f64 * height,
+    report.push_str(&summary);
+    report.push('\n');
+    std::fs::write(&config.output, report)?;
+    println!("{}", summary);
+
+    if failed > 0 {
+        return Err(format!(
+            "{} of {} scenes failed; see {}",
+            failed,
+            scenes.len(),
+            config.output
+        )
+        .into());
+    }
+    Ok(())
+}
+
+struct RawTerminal {
+    saved: String,
+}
+
+fn stty(args: &[&str]) -> Result<String, std::io::Error> {
+    let output = std::process::Command::new("stty")
+        .args(args)
+        .stdin(std::process::Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(
+            "explore needs an interactive terminal",
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl RawTerminal {
+    fn enable() -> Result<RawTerminal, std::io::Error> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo", "min", "0", "time", "1"])?;
+        Ok(RawTerminal { saved })
+    }
+
+    fn size() -> (usize, usize) {
+        stty(&["size"])
+            .ok()
+            .and_then(|size| parse_pair(&size, ' '))
+            .map_or((80, 24), |(rows, columns)| (columns, rows))
     }
 }
 
-fn render(
-    pixels: &mut [u8],
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+        print!("\x1b[?25h");
+        let _ = std::io::stdout().flush();
+    }
+}
+
+enum Key {
+    Pan(f64, f64),
+    Zoom(f64),
+    Iterations(f64),
+    Quit,
+    Other,
+}
+
+fn read_key(input: &mut impl std::io::Read) -> Result<Key, std::io::Error> {
+    let mut next = || -> Result<Option<u8>, std::io::Error> {
+        let mut byte = [0];
+        Ok(match input.read(&mut byte)? {
+            0 => None,
+            _ => Some(byte[0]),
+        })
+    };
+
+    // Reads give up after a tenth of a second, so an Esc with nothing behind
+    // it is a key press of its own rather than the start of an arrow.
+    let first = loop {
+        if let Some(byte) = next()? {
+            break byte;
+        }
+    };
+    Ok(match first {
+        b'\x1b' => match (next()?, next()?) {
+            (None, _) => Key::Quit,
+            (Some(b'['), Some(b'A')) => Key::Pan(0.0, 1.0),
+            (Some(b'['), Some(b'B')) => Key::Pan(0.0, -1.0),
+            (Some(b'['), Some(b'C')) => Key::Pan(1.0, 0.0),
+            (Some(b'['), Some(b'D')) => Key::Pan(-1.0, 0.0),
+            _ => Key::Other,
+        },
+        b'k' | b'w' => Key::Pan(0.0, 1.0),
+        b'j' | b's' => Key::Pan(0.0, -1.0),
+        b'l' | b'd' => Key::Pan(1.0, 0.0),
+        b'h' | b'a' => Key::Pan(-1.0, 0.0),
+        b'+' | b'=' => Key::Zoom(0.5),
+        b'-' | b'_' => Key::Zoom(2.0),
+        b']' => Key::Iterations(2.0),
+        b'[' => Key::Iterations(0.5),
+        b'q' | 3 => Key::Quit,
+        _ => Key::Other,
+    })
+}
+
+fn run_explorer(
+    config: &cli::Config,
+    depth: ColorDepth,
+    pool: &rayon::ThreadPool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (upper_left, lower_right) = config.view.corners(config.bounds);
+    let mut center = (upper_left + lower_right) / 2.0;
+    let mut width = lower_right.re - upper_left.re;
+    let mut options = config.options.clone();
+    let turn = Rotation::new(Complex { re: 0.0, im: 0.0 }, config.view.degrees);
+
+    let terminal = RawTerminal::enable()?;
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    let mut output = std::io::stdout();
+    let (upper_left, lower_right, bounds) = loop {
+        let (columns, rows) = RawTerminal::size();
+        let bounds = (columns.max(1), rows.saturating_sub(1).max(1) * 2);
+        let (upper_left, lower_right) = frame_corners(center, width, bounds);
+        let request = RenderRequest {
+            fractal: config.fractal,
+            bounds,
+            upper_left,
+            lower_right,
+            rotation: Rotation::new(center, config.view.degrees),
+            precision: Precision::Double,
+            center: None,
+            options: options.clone(),
+        };
+        let image = pool.install(|| request.render())?;
+
+        let mut status = format!(
+            "--upper-left {},{} --lower-right {},{} -i {}",
+            upper_left.re, upper_left.im, lower_right.re, lower_right.im, options.limit
+        );
+        status.truncate(columns);
+        write!(
+            output,
+            "\x1b[?25l\x1b[H{}\x1b[2K{}",
+            half_blocks(&image, depth),
+            status
+        )?;
+        output.flush()?;
+
+        match read_key(&mut input)? {
+            Key::Pan(x, y) => center += turn.apply(Complex { re: x, im: y }) * width / 4.0,
+            Key::Zoom(factor) => width *= factor,
+            Key::Iterations(factor) => {
+                options.limit = ((options.limit as f64 * factor) as u32).max(16)
+            }
+            Key::Quit => break (upper_left, lower_right, bounds),
+            Key::Other => {}
+        }
+    };
+    drop(terminal);
+
+    print!("\x1b[2J\x1b[H");
+    println!(
+        "{}",
+        render_command(
+            config.fractal,
+            bounds,
+            upper_left,
+            lower_right,
+            config.view.degrees,
+            &options
+        )
+    );
+    Ok(())
+}
+
+fn render_command(
+    fractal: Fractal,
     bounds: (usize, usize),
     upper_left: Complex<f64>,
     lower_right: Complex<f64>,
-) {
-    assert!(pixels.len() == bounds.0 * bounds.1);
-
-    for row in 0..bounds.1 {
-        for column in 0..bounds.0 {
-            let point = pixel_to_point(bounds, (column, row), upper_left, lower_right);
-            pixels[row * bounds.0 + column] = match escape_time(point, 255) {
-                None => 0,
-                Some(count) => 255 - count as u8,
+    degrees: f64,
+    options: &Options,
+) -> String {
+    let kind = match fractal {
+        Fractal::Mandelbrot => String::new(),
+        Fractal::Julia(c) => format!(" julia {},{}", c.re, c.im),
+        Fractal::Multibrot(degree) => format!(" multibrot {}", degree),
+        Fractal::BurningShip => " burning-ship".to_string(),
+        Fractal::Tricorn => " tricorn".to_string(),
+    };
+    let mut command = format!(
+        "mandelbrot render{} -s {}x{} --upper-left {},{} --lower-right {},{} -i {} -p {}",
+        kind,
+        bounds.0,
+        bounds.1,
+        upper_left.re,
+        upper_left.im,
+        lower_right.re,
+        lower_right.im,
+        options.limit,
+        options.palette
+    );
+    match options.coloring {
+        Coloring::Integer => {}
+        Coloring::Smooth => command.push_str(" --smooth"),
+        Coloring::Distance => command.push_str(" --distance"),
+    }
+    if options.equalize {
+        command.push_str(" --equalize");
+    }
+    if degrees != 0.0 {
+        command.push_str(&format!(" -r {}", degrees));
+    }
+    command
+}
+
+fn run(config: &cli::Config) -> Result<(), Box<dyn std::error::Error>> {
+    let palette = Palette::from_spec(&config.options.palette)?;
+    let bounds = config.bounds;
+
+    let mut configuration = rayon::Configuration::new();
+    if let Some(threads) = config.threads {
+        configuration = configuration.set_num_threads(threads);
+    }
+    let pool = rayon::ThreadPool::new(configuration)?;
+    let samples = config.options.sampling.samples();
+    let scheduler = TileScheduler::new();
+
+    match &config.command {
+        cli::Command::Render => {
+            let (upper_left, lower_right) = config.view.corners(bounds);
+            let center = match &config.view.frame {
+                cli::Frame::Center { text, width, .. } if config.precision != Precision::Double => {
+                    Some(Center {
+                        text: text.clone(),
+                        width: *width,
+                    })
+                }
+                _ => None,
+            };
+            let request = RenderRequest {
+                fractal: config.fractal,
+                bounds,
+                upper_left,
+                lower_right,
+                rotation: config.view.rotation(bounds),
+                precision: config.precision,
+                center,
+                options: config.options.clone(),
+            };
+            let other = match request.options.coloring {
+                Coloring::Integer => Coloring::Smooth,
+                Coloring::Smooth | Coloring::Distance => Coloring::Integer,
+            };
+            let (escapes, other_escapes) = match config.save_escapes {
+                Some(_) => pool.install(|| request.layers_with(other, &scheduler))?,
+                None => (
+                    pool.install(|| request.escapes_with(&scheduler))?,
+                    Vec::new(),
+                ),
             };
+            write_escapes(
+                &config.output,
+                &escapes,
+                bounds,
+                &palette,
+                config.format,
+                &config.options,
+                None,
+            )?;
+            write_metadata(&config.output, config.format, &request)?;
+
+            if let Some(filename) = &config.save_escapes {
+                let mut dump = Dump::new(bounds, samples);
+                dump.layers.push((request.options.coloring, escapes));
+                dump.layers.push((other, other_escapes));
+                write_dump(filename, &dump)?;
+            }
         }
+        cli::Command::Deep => {
+            let (upper_left, lower_right) = config.view.corners(bounds);
+            let (text, width) = match &config.view.frame {
+                cli::Frame::Center { text, width, .. } => (text, *width),
+                cli::Frame::Corners(..) => unreachable!(),
+            };
+            let request = RenderRequest {
+                fractal: config.fractal,
+                bounds,
+                upper_left,
+                lower_right,
+                rotation: config.view.rotation(bounds),
+                precision: Precision::Deep,
+                center: Some(Center {
+                    text: text.clone(),
+                    width,
+                }),
+                options: config.options.clone(),
+            };
+            let escapes = pool.install(|| request.escapes_with(&scheduler))?;
+            write_escapes(
+                &config.output,
+                &escapes,
+                bounds,
+                &palette,
+                config.format,
+                &config.options,
+                None,
+            )?;
+            write_metadata(&config.output, config.format, &request)?;
+        }
+        cli::Command::Sequence { end_zoom, frames } => {
+            let (target, start) = match &config.view.frame {
+                cli::Frame::Center { center, width, .. } => (*center, *width),
+                cli::Frame::Corners(..) => unreachable!(),
+            };
+            let end = cli::DEFAULT_WIDTH / end_zoom;
+            let rotation = Rotation::new(target, config.view.degrees);
+
+            for frame in 0..*frames {
+                let t = if *frames > 1 {
+                    frame as f64 / (frames - 1) as f64
+                } else {
+                    0.0
+                };
+                let width = start * (end / start).powf(t);
+                let (upper_left, lower_right) = frame_corners(target, width, bounds);
+
+                let request = RenderRequest {
+                    fractal: config.fractal,
+                    bounds,
+                    upper_left,
+                    lower_right,
+                    rotation,
+                    precision: Precision::Double,
+                    center: None,
+                    options: config.options.clone(),
+                };
+                let escapes = pool.install(|| request.escapes_with(&scheduler))?;
+                let filename = frame_filename(&config.output, frame);
+                write_escapes(
+                    &filename,
+                    &escapes,
+                    bounds,
+                    &palette,
+                    config.format,
+                    &config.options,
+                    Some(config.options.fixed_range()),
+                )?;
+                write_metadata(&filename, config.format, &request)?;
+            }
+        }
+        cli::Command::Bench => run_benchmark(config),
+        cli::Command::Rerender { input, overrides } => {
+            let mut request = read_metadata(input)?;
+            let old_center = (request.upper_left + request.lower_right) / 2.0;
+            let center = overrides
+                .center
+                .as_ref()
+                .map_or(old_center, |(center, _)| *center);
+            if overrides.size.is_some() || overrides.center.is_some() || overrides.zoom.is_some() {
+                let old_width = match &request.center {
+                    Some(center) => center.width,
+                    None => request.lower_right.re - request.upper_left.re,
+                };
+                let width = old_width / overrides.zoom.unwrap_or(1.0);
+                request.bounds = overrides.size.unwrap_or(request.bounds);
+                let (upper_left, lower_right) = frame_corners(center, width, request.bounds);
+                request.upper_left = upper_left;
+                request.lower_right = lower_right;
+                request.rotation = Rotation::new(center, request.rotation.degrees());
+                if let Some(precise) = &mut request.center {
+                    precise.width = width;
+                    if let Some((_, text)) = &overrides.center {
+                        precise.text = text.clone();
+                    }
+                }
+            }
+
+            let options = &mut request.options;
+            options.limit = overrides.limit.unwrap_or(options.limit);
+            options.coloring = overrides.coloring.unwrap_or(options.coloring);
+            if let Some(palette) = &overrides.palette {
+                options.palette = palette.clone();
+            }
+            options.sampling = config.options.sampling;
+            options.vectorize = config.options.vectorize;
+            options.fill = config.options.fill;
+            options.equalize |= config.options.equalize;
+
+            let palette = Palette::from_spec(&request.options.palette)?;
+            let escapes = pool.install(|| request.escapes_with(&scheduler))?;
+            write_escapes(
+                &config.output,
+                &escapes,
+                request.bounds,
+                &palette,
+                config.format,
+                &request.options,
+                None,
+            )?;
+            write_metadata(&config.output, config.format, &request)?;
+        }
+        cli::Command::Batch { input } => run_batch(config, input, &pool, &scheduler)?,
+        cli::Command::Recolor { input } => {
+            let dump = read_dump(input)?;
+            let coloring = config.options.coloring;
+            let escapes = dump.layer(coloring).ok_or_else(|| {
+                let missing = match coloring {
+                    Coloring::Integer => "integer escape counts",
+                    Coloring::Smooth => "smooth escape values",
+                    Coloring::Distance => "distance estimates",
+                };
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("{}: the dump holds no {}", input, missing),
+                )
+            })?;
+            write_escapes(
+                &config.output,
+                escapes,
+                dump.bounds,
+                &palette,
+                config.format,
+                &config.options,
+                None,
+            )?;
+        }
+        cli::Command::Buddhabrot { density } => {
+            let view = config.view.at(bounds);
+            let counts = pool.install(|| render_density(view, config.fractal, density));
+            if counts.len() == 1 {
+                write_escapes(
+                    &config.output,
+                    &density_values(&counts[0]),
+                    bounds,
+                    &palette,
+                    config.format,
+                    &config.options,
+                    None,
+                )?;
+            } else {
+                let pixels = density_pixels(&counts);
+                write_image(&config.output, &pixels, bounds, ColorType::RGB(8))?;
+            }
+        }
+        cli::Command::Explore { depth } => run_explorer(config, *depth, &pool)?,
+        cli::Command::Tiles { tile_size } => {
+            pool.install(|| render_tiles(config, *tile_size, &palette, &scheduler))?;
+        }
+    }
+
+    if config.stats {
+        eprintln!("{}", scheduler.report());
     }
+
+    Ok(())
 }
 
-use image::png::PNGEncoder;
-use image::ColorType;
-use std::fs::File;
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-fn write_image(
-    filename: &str,
-    pixels: &[u8],
-    bounds: (usize, usize),
-) -> Result<(), std::io::Error> {
-    let output = File::create(filename)?;
+    if args.is_empty() {
+        eprintln!("{}", cli::USAGE);
+        std::process::exit(2);
+    }
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", cli::USAGE);
+        return;
+    }
 
-    let encoder = PNGEncoder::new(output);
-    encoder.encode(
-        &pixels,
-        bounds.0 as u32,
-        bounds.1 as u32,
-        ColorType::Gray(8),
-    )?;
+    let config = match cli::parse_args(&args) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            eprintln!("Run `mandelbrot --help` for usage.");
+            std::process::exit(2);
+        }
+    };
 
-    Ok(())
+    if let Err(error) = run(&config) {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
 }
 
-use rayon::prelude::*;
-use std::io::Write;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    fn parse(line: &str) -> Result<cli::Config, String> {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        cli::parse_args(&args).map_err(|error| error.to_string())
+    }
 
-    if args.len() != 5 {
-        writeln!(
-            std::io::stderr(),
-            "Usage: mandelbrot FILE PIXELS UPPERLEFT LOWERRIGHT"
-        )
+    #[test]
+    fn frame_filename_numbers_before_the_extension() {
+        assert_eq!(frame_filename("zoom.png", 3), "zoom-00003.png");
+        assert_eq!(frame_filename("out.d/zoom", 12), "out.d/zoom-00012");
+        assert_eq!(frame_filename("zoom", 0), "zoom-00000");
+    }
+
+    #[test]
+    fn sequences_zoom_geometrically_towards_the_target() {
+        let dir = std::env::temp_dir().join(format!("sequence-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("zoom.png").to_string_lossy().into_owned();
+        let config = parse(&format!(
+            "sequence -o {} -s 16x12 -c -0.75,0.1 -z 1 --end-zoom 100 --frames 3 -p fire",
+            output
+        ))
         .unwrap();
-        writeln!(
-            std::io::stderr(),
-            "Example: {} mandel.png 1000x750 -1.20,0.35 -1,0.20",
-            args[0]
-        )
+        run(&config).unwrap();
+
+        let widths: Vec<f64> = (0..3)
+            .map(|frame| {
+                let request = read_metadata(&frame_filename(&output, frame)).unwrap();
+                let middle = (request.upper_left + request.lower_right) / 2.0;
+                assert!((middle - Complex { re: -0.75, im: 0.1 }).norm() < 1e-12);
+                assert_eq!(request.options.palette, "fire");
+                request.lower_right.re - request.upper_left.re
+            })
+            .collect();
+        assert!((widths[0] - 4.0).abs() < 1e-12);
+        assert!((widths[1] - 0.4).abs() < 1e-12);
+        assert!((widths[2] - 0.04).abs() < 1e-12);
+        assert!(!Path::new(&frame_filename(&output, 3)).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn explorer_prints_a_command_that_reproduces_the_view() {
+        let options = Options {
+            palette: "fire".to_string(),
+            coloring: Coloring::Smooth,
+            ..Options::default()
+        };
+        let julia = Fractal::Julia(Complex {
+            re: -0.8,
+            im: 0.156,
+        });
+        let command = render_command(
+            julia,
+            (120, 46),
+            Complex { re: -1.5, im: 1.0 },
+            Complex { re: 1.5, im: -1.0 },
+            30.0,
+            &options,
+        );
+        assert_eq!(
+            command,
+            "mandelbrot render julia -0.8,0.156 -s 120x46 --upper-left -1.5,1 --lower-right 1.5,-1 \
+             -i 255 -p fire --smooth -r 30"
+        );
+
+        let args: Vec<String> = command
+            .split_whitespace()
+            .skip(1)
+            .map(String::from)
+            .chain(vec!["-o".to_string(), "out.png".to_string()])
+            .collect();
+        let config = cli::parse_args(&args).unwrap();
+        assert_eq!(config.fractal, julia);
+        assert_eq!(config.bounds, (120, 46));
+        assert_eq!(config.options.palette, "fire");
+        assert_eq!(config.options.coloring, Coloring::Smooth);
+        assert_eq!(config.view.degrees, 30.0);
+    }
+
+    #[test]
+    fn a_lone_escape_quits_the_explorer() {
+        assert!(matches!(read_key(&mut &b"\x1b"[..]), Ok(Key::Quit)));
+        assert!(matches!(
+            read_key(&mut &b"\x1b[D"[..]),
+            Ok(Key::Pan(x, y)) if x == -1.0 && y == 0.0
+        ));
+        assert!(matches!(read_key(&mut &b"\x1b[Z"[..]), Ok(Key::Other)));
+    }
+
+    fn tile_dir(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("tiles-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.to_string_lossy().into_owned()
+    }
+
+    fn run_tiles(dir: &str, extra: &str) -> Result<(), std::io::Error> {
+        let config = parse(&format!(
+            "tiles -o {} -s 50x40 --tile-size 16 -p fire {}",
+            dir, extra
+        ))
         .unwrap();
-        std::process::exit(1);
+        let palette = Palette::from_spec(&config.options.palette).unwrap();
+        render_tiles(&config, 16, &palette, &TileScheduler::new())
     }
 
-    let bounds = parse_pair(&args[2], 'x').expect("error parsing image dimensions");
-    let upper_left = parse_complex(&args[3]).expect("error parsing upper left corner point");
-    let lower_right = parse_complex(&args[4]).expect("error parsing lower right corner point");
+    #[test]
+    fn interrupted_tiles_resume_without_rerendering() {
+        let dir = tile_dir("resume");
+        run_tiles(&dir, "").unwrap();
+        let manifest = Path::new(&dir).join("manifest.txt");
+        let tile = |row: usize, column: usize| {
+            Path::new(&dir).join(format!("tile-{:04}-{:04}.png", row, column))
+        };
+        let text = std::fs::read_to_string(&manifest).unwrap();
+        assert_eq!(text.matches("done ").count(), 12);
+        let last = std::fs::read(tile(2, 3)).unwrap();
+
+        let header_lines = text.lines().take_while(|line| !line.starts_with("done "));
+        let mut interrupted: String = header_lines.map(|line| format!("{}\n", line)).collect();
+        interrupted.push_str("done 0 0\ndone 0 1\ndone 2");
+        std::fs::write(&manifest, interrupted).unwrap();
+        std::fs::write(tile(0, 0), "finished").unwrap();
+        std::fs::remove_file(tile(2, 3)).unwrap();
 
-    let mut pixels = vec![0; bounds.0 * bounds.1];
+        run_tiles(&dir, "").unwrap();
+        assert_eq!(std::fs::read(tile(0, 0)).unwrap(), b"finished");
+        assert_eq!(std::fs::read(tile(2, 3)).unwrap(), last);
+        let resumed = std::fs::read_to_string(&manifest).unwrap();
+        assert_eq!(resumed.matches("done ").count(), 12);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 
-    {
-        let bands: Vec<(usize, &mut [u8])> = pixels.chunks_mut(bounds.0).enumerate().collect();
+    #[test]
+    fn stale_or_corrupt_manifests_are_rejected() {
+        let dir = tile_dir("stale");
+        run_tiles(&dir, "").unwrap();
+        let error = run_tiles(&dir, "-i 300").unwrap_err().to_string();
+        assert!(error.ends_with("belongs to a different render; use another directory"));
 
-        bands.into_par_iter().weight_max().for_each(|(i, band)| {
-            let top = i;
-            let band_bounds = (bounds.0, 1);
-            let band_upper_left = pixel_to_point(bounds, (0, top), upper_left, lower_right);
-            let band_lower_right =
-                pixel_to_point(bounds, (bounds.0, top + 1), upper_left, lower_right);
-            render(band, band_bounds, band_upper_left, band_lower_right);
-        });
+        let manifest = Path::new(&dir).join("manifest.txt");
+        let mut text = std::fs::read_to_string(&manifest).unwrap();
+        text.push_str("done 1 x\n");
+        std::fs::write(&manifest, text).unwrap();
+        let error = run_tiles(&dir, "").unwrap_err().to_string();
+        assert!(error.ends_with("has a corrupt entry `done 1 x`"));
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
-    write_image(&args[1], &pixels, bounds).expect("error writing PNG file");
+    #[test]
+    fn render_is_the_default_command() {
+        let config = parse("-o out.png").unwrap();
+        assert_eq!(config.command, cli::Command::Render);
+        assert_eq!(config.fractal, Fractal::Mandelbrot);
+        assert_eq!(config.bounds, (800, 600));
+        assert_eq!(config.format, Format::Png);
+        assert_eq!(
+            config.view.corners(config.bounds),
+            (
+                Complex { re: -2.75, im: 1.5 },
+                Complex { re: 1.25, im: -1.5 }
+            )
+        );
+        assert_eq!(config.options.limit, 255);
+        assert_eq!(config.options.coloring, Coloring::Integer);
+    }
+
+    #[test]
+    fn kinds_and_flags_are_parsed() {
+        let config = parse("render julia -0.8,0.156 -o out.ppm -s 100x50 -i 900 --smooth").unwrap();
+        assert_eq!(
+            config.fractal,
+            Fractal::Julia(Complex {
+                re: -0.8,
+                im: 0.156
+            })
+        );
+        assert_eq!(config.bounds, (100, 50));
+        assert_eq!(config.format, Format::Ppm);
+        assert_eq!(config.options.limit, 900);
+        assert_eq!(config.options.coloring, Coloring::Smooth);
+        assert_eq!(config.precision, Precision::Double);
+
+        let config = parse("-o out.png --precision dd").unwrap();
+        assert_eq!(config.precision, Precision::DoubleDouble);
+
+        let config = parse("multibrot 3 -o out.png --upper-left -1,1 --lower-right 1,-1").unwrap();
+        assert_eq!(config.fractal, Fractal::Multibrot(3));
+        assert_eq!(
+            config.view.corners(config.bounds),
+            (Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 })
+        );
+    }
+
+    #[test]
+    fn errors_name_the_offending_argument() {
+        let error = |line: &str| {
+            let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+            cli::parse_args(&args).err().unwrap()
+        };
+        assert_eq!(
+            error("-o out.png -s"),
+            cli::CliError::MissingValue("-s".to_string())
+        );
+        assert_eq!(
+            error("-o out.png -i many"),
+            cli::CliError::InvalidValue {
+                flag: "-i".to_string(),
+                value: "many".to_string(),
+                expected: "a positive integer",
+            }
+        );
+        assert_eq!(
+            error("-o out.png --threads 0"),
+            cli::CliError::InvalidValue {
+                flag: "--threads".to_string(),
+                value: "0".to_string(),
+                expected: "a positive integer",
+            }
+        );
+        assert_eq!(
+            error("-o out.png 800x600"),
+            cli::CliError::UnexpectedArgument("800x600".to_string())
+        );
+        assert_eq!(error("-p fire"), cli::CliError::MissingFlag("--output"));
+
+        let config = parse("-o out.tga -f png -t 3 -c -0.5,0.25 -w 2").unwrap();
+        assert_eq!(config.format, Format::Png);
+        assert_eq!(config.threads, Some(3));
+        assert_eq!(
+            config.view.frame,
+            cli::Frame::Center {
+                center: Complex { re: -0.5, im: 0.25 },
+                text: "-0.5,0.25".to_string(),
+                width: 2.0
+            }
+        );
+    }
+
+    #[test]
+    fn centered_views_keep_pixels_square_and_rotate_about_the_center() {
+        let config = parse("-o out.png -s 400x100 -c -0.5,0.25 -z 2 -r 90").unwrap();
+        let view = config.view.at(config.bounds);
+        assert_eq!(
+            (view.upper_left, view.lower_right),
+            (Complex { re: -1.5, im: 0.5 }, Complex { re: 0.5, im: 0.0 })
+        );
+        let pixel = (view.lower_right.re - view.upper_left.re) / 400.0;
+        assert_eq!(pixel, (view.upper_left.im - view.lower_right.im) / 100.0);
+
+        let center = Complex { re: -0.5, im: 0.25 };
+        let corner = view.sample_to_point((0.0, 0.0));
+        assert!(
+            (corner
+                - (center
+                    + Complex {
+                        re: -0.25,
+                        im: -1.0
+                    }))
+            .norm()
+                < 1e-12
+        );
+        assert!((view.sample_to_point((200.0, 50.0)) - center).norm() < 1e-12);
+
+        let config = parse("-o out.png -s 400x100 -c -0.5,0.25 -w 1").unwrap();
+        let view = config.view.at(config.bounds);
+        assert_eq!(view.lower_right.re - view.upper_left.re, 1.0);
+        assert_eq!(view.upper_left.im - view.lower_right.im, 0.25);
+        assert_eq!(view.rotation, Rotation::new(center, 0.0));
+    }
+
+    #[test]
+    fn mistakes_are_rejected() {
+        assert_eq!(
+            parse("-o out.png -s 0x10").err().unwrap(),
+            "invalid value `0x10` for `-s`: expected a size like 1000x750"
+        );
+        assert_eq!(
+            parse("multibrot 1 -o out.png").err().unwrap(),
+            "invalid value `1` for `multibrot`: expected an integer degree of at least 2"
+        );
+        assert_eq!(
+            parse("-s 10x10").err().unwrap(),
+            "missing required `--output`"
+        );
+        assert_eq!(
+            parse("-o out.png -z 2 -w 1").err().unwrap(),
+            "`--zoom` cannot be used with `--width`"
+        );
+        assert_eq!(
+            parse("-o out.png --smooth --distance").err().unwrap(),
+            "`--smooth` cannot be used with `--distance`"
+        );
+        assert_eq!(
+            parse("deep -o out.png --upper-left -1,1 --lower-right 1,-1")
+                .err()
+                .unwrap(),
+            "deep: the view must be given with `--center` and `--zoom` or `--width`"
+        );
+        assert_eq!(
+            parse("-o out.png --tile-size 64").err().unwrap(),
+            "render: `--tile-size` only applies to tiles"
+        );
+        assert_eq!(
+            parse("-o out.png -c 1e99999999,0").err().unwrap(),
+            "invalid value `1e99999999,0` for `-c`: expected a point like -1.20,0.35"
+        );
+        assert_eq!(
+            parse("-o out.png --precision f32 --fill").err().unwrap(),
+            "`--precision` cannot be used with `--fill`"
+        );
+        assert_eq!(
+            parse("-o out.raw --smooth").err().unwrap(),
+            "render: raw files hold integer escape counts; drop `--smooth` and `--distance`"
+        );
+        for line in &[
+            "-o out.png -z inf",
+            "-o out.png --zoom inf",
+            "-o out.png -w inf",
+            "sequence -o out.png --end-zoom inf",
+        ] {
+            assert!(parse(line)
+                .err()
+                .unwrap()
+                .starts_with("invalid value `inf`"));
+        }
+    }
 }
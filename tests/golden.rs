@@ -0,0 +1,192 @@
+use num::Complex;
+use rayon::{Configuration, ThreadPool};
+use rust_sandbox::fractal::{frame_corners, Coloring, Fractal, RenderRequest, Rotation};
+use std::path::PathBuf;
+
+const SIZE: (usize, usize) = (64, 48);
+
+struct Golden {
+    name: &'static str,
+    fractal: Fractal,
+    center: (f64, f64),
+    width: f64,
+    degrees: f64,
+    limit: u32,
+    coloring: Coloring,
+    palette: &'static str,
+    escape_hash: u64,
+}
+
+const GOLDEN: [Golden; 6] = [
+    Golden {
+        name: "mandelbrot",
+        fractal: Fractal::Mandelbrot,
+        center: (-0.75, 0.0),
+        width: 4.0,
+        degrees: 0.0,
+        limit: 255,
+        coloring: Coloring::Integer,
+        palette: "gray",
+        escape_hash: 0x688e_5461_7154_7b85,
+    },
+    Golden {
+        name: "seahorse",
+        fractal: Fractal::Mandelbrot,
+        center: (-0.7436, 0.1318),
+        width: 0.01,
+        degrees: 0.0,
+        limit: 1000,
+        coloring: Coloring::Smooth,
+        palette: "fire",
+        escape_hash: 0xd3e1_f8f7_1a27_a788,
+    },
+    Golden {
+        name: "julia",
+        fractal: Fractal::Julia(Complex {
+            re: -0.8,
+            im: 0.156,
+        }),
+        center: (0.0, 0.0),
+        width: 3.2,
+        degrees: 0.0,
+        limit: 500,
+        coloring: Coloring::Smooth,
+        palette: "ocean",
+        escape_hash: 0x7840_0ff0_13f9_a9b9,
+    },
+    Golden {
+        name: "burning-ship",
+        fractal: Fractal::BurningShip,
+        center: (-1.755, -0.03),
+        width: 0.12,
+        degrees: 0.0,
+        limit: 255,
+        coloring: Coloring::Integer,
+        palette: "ultra",
+        escape_hash: 0xd75f_7c15_f1b8_1053,
+    },
+    Golden {
+        name: "multibrot",
+        fractal: Fractal::Multibrot(3),
+        center: (0.0, 0.0),
+        width: 3.0,
+        degrees: 30.0,
+        limit: 255,
+        coloring: Coloring::Smooth,
+        palette: "rainbow",
+        escape_hash: 0x163a_cfbe_4c49_2f61,
+    },
+    Golden {
+        name: "distance",
+        fractal: Fractal::Mandelbrot,
+        center: (-0.75, 0.0),
+        width: 3.0,
+        degrees: 0.0,
+        limit: 500,
+        coloring: Coloring::Distance,
+        palette: "gray",
+        escape_hash: 0x8f29_76d3_258d_e005,
+    },
+];
+
+fn request(golden: &Golden) -> RenderRequest {
+    let center = Complex {
+        re: golden.center.0,
+        im: golden.center.1,
+    };
+    let (upper_left, lower_right) = frame_corners(center, golden.width, SIZE);
+    let mut request = RenderRequest::new(SIZE, upper_left, lower_right);
+    request.fractal = golden.fractal;
+    request.rotation = Rotation::new(center, golden.degrees);
+    request.options.limit = golden.limit;
+    request.options.coloring = golden.coloring;
+    request.options.palette = golden.palette.to_string();
+    request
+}
+
+fn reference(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.png", name))
+}
+
+fn updating() -> bool {
+    std::env::var_os("GOLDEN_UPDATE").is_some()
+}
+
+fn escape_hash(escapes: &[Option<f32>]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for escape in escapes {
+        let bits = escape.map_or(u32::MAX, f32::to_bits);
+        for byte in bits.to_le_bytes().iter() {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+#[test]
+fn renders_match_the_reference_images() {
+    for golden in GOLDEN.iter() {
+        let image = request(golden).render().unwrap();
+        let path = reference(golden.name);
+        if updating() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            image.save(path.to_str().unwrap()).unwrap();
+            continue;
+        }
+
+        let expected = image::open(&path)
+            .unwrap_or_else(|error| panic!("{}: {}", path.display(), error))
+            .raw_pixels();
+        let differing = expected
+            .iter()
+            .zip(&image.pixels)
+            .filter(|(expected, actual)| expected != actual)
+            .count();
+        assert!(
+            expected.len() == image.pixels.len() && differing == 0,
+            "{}: {} of {} bytes differ from {}",
+            golden.name,
+            differing,
+            image.pixels.len(),
+            path.display()
+        );
+    }
+}
+
+#[test]
+fn escape_values_match_on_every_kernel_and_thread_count() {
+    for golden in GOLDEN.iter() {
+        let mut scalar = request(golden);
+        scalar.options.vectorize = false;
+        let vectorized = request(golden);
+        let pool = ThreadPool::new(Configuration::new().set_num_threads(3)).unwrap();
+
+        let hashes = [
+            escape_hash(&scalar.escapes().unwrap()),
+            escape_hash(&vectorized.escapes().unwrap()),
+            escape_hash(&pool.install(|| vectorized.escapes().unwrap())),
+        ];
+        if updating() {
+            println!("{}: {:#018x}", golden.name, hashes[0]);
+        }
+        let expected = if updating() {
+            hashes[0]
+        } else {
+            golden.escape_hash
+        };
+        for (kernel, &hash) in ["scalar", "simd", "three threads"].iter().zip(&hashes) {
+            assert!(
+                hash == expected,
+                "{} ({}): escape hash {:#018x}, expected {:#018x}",
+                golden.name,
+                kernel,
+                hash,
+                expected
+            );
+        }
+    }
+}